@@ -1,6 +1,11 @@
 use crate::session_manager::SessionManager;
-use crate::ssh::{AuthMethod, ForwardPort, SshConfig};
+use crate::ssh::ssh_config::{self, ConfigFile};
+use crate::ssh::{
+    AlgorithmPreferences, AuthMethod, AuthReport, AuthenticationFailed, DynamicForwardPort, ExitSignal, ForwardInfo, ForwardPort, ForwardSpec,
+    HostKeyInfo, JumpHost, NegotiatedAlgorithms, ProxyConfig, RemoteForwardPort, SshConfig, StreamLocalForwardPort,
+};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::sync::Arc;
 use tauri::State;
 
@@ -14,7 +19,65 @@ pub struct ConnectRequest {
     pub password: Option<String>,
     pub key_path: Option<String>,
     pub passphrase: Option<String>,
+    pub cert_path: Option<String>,
+    /// Private key text for the `privatekey` auth method
+    pub private_key: Option<String>,
     pub forward_ports: Option<Vec<ForwardPort>>,
+    pub remote_forward_ports: Option<Vec<RemoteForwardPort>>,
+    pub dynamic_forward_ports: Option<Vec<DynamicForwardPort>>,
+    pub stream_local_forward_ports: Option<Vec<StreamLocalForwardPort>>,
+    pub jump_hosts: Option<Vec<JumpHost>>,
+    pub proxy_command: Option<String>,
+    pub proxy: Option<ProxyConfig>,
+    pub keepalive_interval: Option<u64>,
+    pub keepalive_max: Option<usize>,
+    pub auto_reconnect: Option<bool>,
+    pub algorithms: Option<AlgorithmPreferences>,
+    pub fallback_auth_methods: Option<Vec<AuthMethod>>,
+    pub connect_timeout: Option<u64>,
+    pub auth_timeout: Option<u64>,
+    pub x11_forwarding: Option<bool>,
+    pub agent_forwarding: Option<bool>,
+    pub confirm_agent_use: Option<bool>,
+    pub env: Option<HashMap<String, String>>,
+    pub forward_locale: Option<bool>,
+}
+
+/// A `Host` entry from ~/.ssh/config, resolved for display
+#[derive(Debug, Serialize)]
+pub struct SshConfigHost {
+    pub alias: String,
+    pub host: String,
+    pub port: u16,
+    pub username: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ConnectResponse {
+    pub success: bool,
+    pub output: Option<String>,
+    pub error: Option<String>,
+    /// Algorithms negotiated with the server
+    pub algorithms: Option<NegotiatedAlgorithms>,
+    /// Which authentication method succeeded and why earlier ones failed,
+    /// or why every method failed
+    pub auth: Option<AuthReport>,
+    /// Forwards started with the connection, including any that failed to bind
+    pub forwards: Option<Vec<ForwardInfo>>,
+}
+
+/// What the server presented on connect, so users can verify it out-of-band
+#[derive(Debug, Serialize)]
+pub struct SessionDetails {
+    pub host: String,
+    pub port: u16,
+    pub username: String,
+    pub connected: bool,
+    pub host_key: Option<HostKeyInfo>,
+    /// Pre-authentication banner sent by the server
+    pub banner: Option<String>,
+    pub algorithms: Option<NegotiatedAlgorithms>,
+    pub auth: Option<AuthReport>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -74,7 +137,7 @@ pub struct CommandResponse {
 pub async fn ssh_connect(
     request: ConnectRequest,
     state: State<'_, Arc<SessionManager>>,
-) -> Result<CommandResponse, String> {
+) -> Result<ConnectResponse, String> {
     let auth_method = match request.auth_method.as_str() {
         "password" => AuthMethod::Password {
             password: request.password.ok_or("Password required")?,
@@ -82,7 +145,14 @@ pub async fn ssh_connect(
         "publickey" => AuthMethod::PublicKey {
             key_path: request.key_path.ok_or("Key path required")?,
             passphrase: request.passphrase,
+            cert_path: request.cert_path,
         },
+        "privatekey" => AuthMethod::PrivateKey {
+            private_key: request.private_key.ok_or("Private key required")?,
+            passphrase: request.passphrase,
+        },
+        "agent" => AuthMethod::Agent,
+        "keyboard-interactive" => AuthMethod::KeyboardInteractive,
         _ => return Err("Invalid auth method".to_string()),
     };
 
@@ -92,20 +162,167 @@ pub async fn ssh_connect(
         username: request.username,
         auth_method,
         forward_ports: request.forward_ports,
+        remote_forward_ports: request.remote_forward_ports,
+        dynamic_forward_ports: request.dynamic_forward_ports,
+        forward_ids: Vec::new(),
+        stream_local_forward_ports: request.stream_local_forward_ports,
+        jump_hosts: request.jump_hosts,
+        proxy_command: request.proxy_command,
+        proxy: request.proxy,
+        keepalive_interval: request.keepalive_interval,
+        keepalive_max: request.keepalive_max,
+        auto_reconnect: request.auto_reconnect,
+        algorithms: request.algorithms,
+        fallback_auth_methods: request.fallback_auth_methods,
+        connect_timeout: request.connect_timeout,
+        auth_timeout: request.auth_timeout,
+        x11_forwarding: request.x11_forwarding,
+        agent_forwarding: request.agent_forwarding,
+        confirm_agent_use: request.confirm_agent_use,
+        env: request.env,
+        forward_locale: request.forward_locale,
     };
 
-    match state.create_session(request.session_id.clone(), config).await {
-        Ok(_) => Ok(CommandResponse {
-            success: true,
-            output: Some(format!("Connected: {}", request.session_id)),
-            error: None,
-        }),
-        Err(e) => Ok(CommandResponse {
+    connect_session(&state, request.session_id, config).await
+}
+
+/// Create a session and report the outcome along with the negotiated algorithms
+async fn connect_session(
+    state: &State<'_, Arc<SessionManager>>,
+    session_id: String,
+    config: SshConfig,
+) -> Result<ConnectResponse, String> {
+    if let Err(e) = state.create_session(session_id.clone(), config).await {
+        return Ok(ConnectResponse {
             success: false,
             output: None,
             error: Some(e.to_string()),
-        }),
+            algorithms: None,
+            // Lets the frontend show why each method was turned down
+            auth: e.downcast_ref::<AuthenticationFailed>().map(|failed| failed.report.clone()),
+            forwards: None,
+        });
+    }
+
+    let (algorithms, auth, forwards) = match state.get_session(&session_id).await {
+        Some(client) => {
+            let client = client.read().await;
+            (
+                client.negotiated_algorithms().cloned(),
+                client.auth_report().cloned(),
+                Some(client.forwards()),
+            )
+        }
+        None => (None, None, None),
+    };
+    Ok(ConnectResponse {
+        success: true,
+        output: Some(format!("Connected: {}", session_id)),
+        error: None,
+        algorithms,
+        auth,
+        forwards,
+    })
+}
+
+fn load_ssh_config() -> Result<ConfigFile, String> {
+    match ssh_config::default_path() {
+        Some(path) => ConfigFile::load(&path).map_err(|e| e.to_string()),
+        None => Ok(ConfigFile::default()),
+    }
+}
+
+fn with_passphrase(method: AuthMethod, passphrase: &Option<String>) -> AuthMethod {
+    match method {
+        AuthMethod::PublicKey { key_path, cert_path, .. } => AuthMethod::PublicKey {
+            key_path,
+            passphrase: passphrase.clone(),
+            cert_path,
+        },
+        other => other,
+    }
+}
+
+#[tauri::command]
+pub async fn ssh_config_hosts() -> Result<Vec<SshConfigHost>, String> {
+    let config = load_ssh_config()?;
+
+    let mut hosts = Vec::new();
+    for alias in config.aliases() {
+        match config.resolve(&alias) {
+            Ok(resolved) => hosts.push(SshConfigHost {
+                alias,
+                host: resolved.host,
+                port: resolved.port,
+                username: resolved.username,
+            }),
+            Err(e) => tracing::warn!("Skipping ssh config host {}: {}", alias, e),
+        }
+    }
+    Ok(hosts)
+}
+
+#[tauri::command]
+pub async fn ssh_connect_alias(
+    session_id: String,
+    alias: String,
+    password: Option<String>,
+    passphrase: Option<String>,
+    state: State<'_, Arc<SessionManager>>,
+) -> Result<ConnectResponse, String> {
+    let mut config = match load_ssh_config().and_then(|c| c.resolve(&alias).map_err(|e| e.to_string())) {
+        Ok(config) => config,
+        Err(e) => {
+            return Ok(ConnectResponse {
+                success: false,
+                output: None,
+                error: Some(e),
+                algorithms: None,
+                auth: None,
+                forwards: None,
+            })
+        }
+    };
+
+    // The config names the keys; secrets come from the caller
+    config.auth_method = with_passphrase(config.auth_method, &passphrase);
+    let mut fallbacks: Vec<AuthMethod> = config
+        .fallback_auth_methods
+        .take()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|method| with_passphrase(method, &passphrase))
+        .collect();
+    if let Some(password) = password {
+        fallbacks.push(AuthMethod::Password { password });
     }
+    config.fallback_auth_methods = (!fallbacks.is_empty()).then_some(fallbacks);
+
+    connect_session(&state, session_id, config).await
+}
+
+#[tauri::command]
+pub async fn ssh_session_details(
+    session_id: String,
+    state: State<'_, Arc<SessionManager>>,
+) -> Result<SessionDetails, String> {
+    let session = state
+        .get_session(&session_id)
+        .await
+        .ok_or("Session not found")?;
+
+    let client = session.read().await;
+    let config = client.config().ok_or("Session is not connected")?;
+    Ok(SessionDetails {
+        host: config.host.clone(),
+        port: config.port,
+        username: config.username.clone(),
+        connected: client.is_connected(),
+        host_key: client.host_key().cloned(),
+        banner: client.banner().map(str::to_string),
+        algorithms: client.negotiated_algorithms().cloned(),
+        auth: client.auth_report().cloned(),
+    })
 }
 
 #[tauri::command]
@@ -128,6 +345,70 @@ pub async fn ssh_cancel_connect(
     }
 }
 
+#[tauri::command]
+pub async fn ssh_host_key_response(
+    session_id: String,
+    accept: bool,
+    state: State<'_, Arc<SessionManager>>,
+) -> Result<CommandResponse, String> {
+    if state.respond_host_key(&session_id, accept).await {
+        Ok(CommandResponse {
+            success: true,
+            output: Some(if accept { "Host key accepted" } else { "Host key rejected" }.to_string()),
+            error: None,
+        })
+    } else {
+        Ok(CommandResponse {
+            success: false,
+            output: None,
+            error: Some("No pending host key verification".to_string()),
+        })
+    }
+}
+
+#[tauri::command]
+pub async fn ssh_keyboard_interactive_response(
+    session_id: String,
+    responses: Vec<String>,
+    state: State<'_, Arc<SessionManager>>,
+) -> Result<CommandResponse, String> {
+    if state.respond_keyboard_interactive(&session_id, responses).await {
+        Ok(CommandResponse {
+            success: true,
+            output: Some("Responses sent".to_string()),
+            error: None,
+        })
+    } else {
+        Ok(CommandResponse {
+            success: false,
+            output: None,
+            error: Some("No pending keyboard-interactive prompt".to_string()),
+        })
+    }
+}
+
+#[tauri::command]
+pub async fn ssh_agent_forward_response(
+    session_id: String,
+    request_id: u64,
+    allow: bool,
+    state: State<'_, Arc<SessionManager>>,
+) -> Result<CommandResponse, String> {
+    if state.respond_agent_forward(&session_id, request_id, allow).await {
+        Ok(CommandResponse {
+            success: true,
+            output: Some(if allow { "Agent use allowed" } else { "Agent use refused" }.to_string()),
+            error: None,
+        })
+    } else {
+        Ok(CommandResponse {
+            success: false,
+            output: None,
+            error: Some("No pending agent forwarding request".to_string()),
+        })
+    }
+}
+
 #[tauri::command]
 pub async fn ssh_disconnect(
     session_id: String,
@@ -147,12 +428,109 @@ pub async fn ssh_disconnect(
     }
 }
 
+#[tauri::command]
+pub async fn ssh_add_forward(
+    session_id: String,
+    forward: ForwardSpec,
+    state: State<'_, Arc<SessionManager>>,
+) -> Result<ForwardInfo, String> {
+    let session = state
+        .get_session(&session_id)
+        .await
+        .ok_or("Session not found")?;
+
+    let mut client = session.write().await;
+    client.add_forward(forward).await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn ssh_remove_forward(
+    session_id: String,
+    forward_id: u64,
+    state: State<'_, Arc<SessionManager>>,
+) -> Result<CommandResponse, String> {
+    let session = state
+        .get_session(&session_id)
+        .await
+        .ok_or("Session not found")?;
+
+    let mut client = session.write().await;
+    match client.remove_forward(forward_id).await {
+        Ok(_) => Ok(CommandResponse {
+            success: true,
+            output: Some(format!("Forward {} removed", forward_id)),
+            error: None,
+        }),
+        Err(e) => Ok(CommandResponse {
+            success: false,
+            output: None,
+            error: Some(e.to_string()),
+        }),
+    }
+}
+
+#[tauri::command]
+pub async fn ssh_list_forwards(
+    session_id: String,
+    state: State<'_, Arc<SessionManager>>,
+) -> Result<Vec<ForwardInfo>, String> {
+    let session = state
+        .get_session(&session_id)
+        .await
+        .ok_or("Session not found")?;
+
+    let client = session.read().await;
+    Ok(client.forwards())
+}
+
+/// Emit the session's forward stats as events every `interval_ms`; 0 or none stops them
+#[tauri::command]
+pub async fn ssh_watch_forward_stats(
+    session_id: String,
+    interval_ms: Option<u64>,
+    state: State<'_, Arc<SessionManager>>,
+) -> Result<CommandResponse, String> {
+    let interval = interval_ms
+        .filter(|&ms| ms > 0)
+        .map(std::time::Duration::from_millis);
+    match state.watch_forward_stats(&session_id, interval).await {
+        Ok(_) => Ok(CommandResponse {
+            success: true,
+            output: Some(match interval {
+                Some(interval) => format!("Reporting forward stats every {}ms", interval.as_millis()),
+                None => "Stopped reporting forward stats".to_string(),
+            }),
+            error: None,
+        }),
+        Err(e) => Ok(CommandResponse {
+            success: false,
+            output: None,
+            error: Some(e.to_string()),
+        }),
+    }
+}
+
+/// `CommandResponse` plus the command's separate output streams and how it ended
+#[derive(Debug, Serialize)]
+pub struct ExecuteCommandResponse {
+    pub success: bool,
+    pub output: Option<String>,
+    pub error: Option<String>,
+    pub stdout: String,
+    pub stderr: String,
+    pub exit_code: Option<u32>,
+    pub exit_signal: Option<ExitSignal>,
+    pub duration_ms: u64,
+    /// Environment variables the server refused to set
+    pub rejected_env: Vec<String>,
+}
+
 #[tauri::command]
 pub async fn ssh_execute_command(
     session_id: String,
     command: String,
     state: State<'_, Arc<SessionManager>>,
-) -> Result<CommandResponse, String> {
+) -> Result<ExecuteCommandResponse, String> {
     let session = state
         .get_session(&session_id)
         .await
@@ -163,28 +541,107 @@ pub async fn ssh_execute_command(
     // Transform interactive commands to batch mode
     let transformed_command = transform_interactive_command(&command);
     
-    match client.execute_command(&transformed_command).await {
-        Ok(output) => Ok(CommandResponse {
-            success: true,
-            output: Some(output),
-            error: None,
-        }),
+    let result = match client.exec(&transformed_command).await {
+        Ok(result) => result,
         Err(e) => {
-            // Check if it's an interactive command that failed
-            let error_msg = if is_interactive_command(&command) {
-                format!("{}\n\nNote: Interactive commands like '{}' may not work in this terminal. Try using batch mode alternatives.", 
-                    e, 
-                    get_command_name(&command))
-            } else {
-                e.to_string()
-            };
-            
-            Ok(CommandResponse {
+            return Ok(ExecuteCommandResponse {
                 success: false,
                 output: None,
-                error: Some(error_msg),
+                error: Some(interactive_command_error(&command, e.to_string())),
+                stdout: String::new(),
+                stderr: String::new(),
+                exit_code: None,
+                exit_signal: None,
+                duration_ms: 0,
+                rejected_env: Vec::new(),
+            });
+        }
+    };
+
+    let stdout = String::from_utf8_lossy(&result.stdout).into_owned();
+    let stderr = String::from_utf8_lossy(&result.stderr).into_owned();
+    let error = result
+        .error_message()
+        .map(|message| interactive_command_error(&command, message));
+    Ok(ExecuteCommandResponse {
+        success: error.is_none(),
+        output: error.is_none().then(|| stdout.clone()),
+        error,
+        stdout,
+        stderr,
+        exit_code: result.status.exit_code,
+        exit_signal: result.status.exit_signal,
+        duration_ms: result.duration_ms,
+        rejected_env: result.rejected_env,
+    })
+}
+
+/// Start a command whose output arrives as `ssh-exec-output` events and whose exit
+/// status arrives as an `ssh-exec-exit` event, all tagged with `execution_id`
+#[tauri::command]
+pub async fn ssh_execute_command_streaming(
+    session_id: String,
+    execution_id: String,
+    command: String,
+    state: State<'_, Arc<SessionManager>>,
+) -> Result<CommandResponse, String> {
+    let transformed_command = transform_interactive_command(&command);
+    match state.start_execution(&session_id, &execution_id, &transformed_command).await {
+        Ok(rejected_env) => {
+            let mut output = format!("Started execution {}", execution_id);
+            if !rejected_env.is_empty() {
+                output.push_str(&format!(
+                    " (server rejected environment variables: {})",
+                    rejected_env.join(", ")
+                ));
+            }
+            Ok(CommandResponse {
+                success: true,
+                output: Some(output),
+                error: None,
             })
         }
+        Err(e) => Ok(CommandResponse {
+            success: false,
+            output: None,
+            error: Some(interactive_command_error(&command, e.to_string())),
+        }),
+    }
+}
+
+/// Stop a streamed command by sending it `signal` (e.g. `INT`), or by closing its channel
+#[tauri::command]
+pub async fn ssh_cancel_command(
+    session_id: String,
+    execution_id: String,
+    signal: Option<String>,
+    state: State<'_, Arc<SessionManager>>,
+) -> Result<CommandResponse, String> {
+    match state.cancel_execution(&session_id, &execution_id, signal.as_deref()).await {
+        Ok(_) => Ok(CommandResponse {
+            success: true,
+            output: Some(match signal {
+                Some(signal) => format!("Sent {} to execution {}", signal, execution_id),
+                None => format!("Closed execution {}", execution_id),
+            }),
+            error: None,
+        }),
+        Err(e) => Ok(CommandResponse {
+            success: false,
+            output: None,
+            error: Some(e.to_string()),
+        }),
+    }
+}
+
+// Check if it's an interactive command that failed
+fn interactive_command_error(command: &str, error: String) -> String {
+    if is_interactive_command(command) {
+        format!("{}\n\nNote: Interactive commands like '{}' may not work in this terminal. Try using batch mode alternatives.", 
+            error, 
+            get_command_name(command))
+    } else {
+        error
     }
 }
 
@@ -273,7 +730,7 @@ pub async fn get_system_stats(
     // Combined memory, swap, disk, and uptime command
     let combined_cmd = "echo \"$(free -m | awk 'NR==2{printf \"%s %s %s %s \", $2,$3,$4,$7} NR==3{printf \"%s %s %s \", $2,$3,$4}') $(df -h / | awk 'NR==2{printf \"%s %s %s %s\", $2,$3,$4,$5}')\" && (uptime -p 2>/dev/null || uptime | awk '{print $3\" \"$4}')";
     let combined_output = client.execute_command(combined_cmd).await.unwrap_or_default();
-    let combined_parts: Vec<&str> = combined_output.trim().split_whitespace().collect();
+    let combined_parts: Vec<&str> = combined_output.split_whitespace().collect();
 
     // Parse memory stats (first 4 values)
     let memory = MemoryStats {
@@ -304,7 +761,7 @@ pub async fn get_system_stats(
 
     // Parse uptime (remaining parts)
     let uptime_parts = &combined_parts[11..];
-    let uptime = if uptime_parts.len() > 0 {
+    let uptime = if !uptime_parts.is_empty() {
         uptime_parts.join(" ")
     } else {
         "Unknown".to_string()
@@ -826,19 +1283,19 @@ date +%s%3N 2>/dev/null || echo 0
 
             // Split by sections
             let synrecv_split: Vec<&str> = output.split("---SYNRECV---").collect();
-            let ss_s_output = synrecv_split.get(0).unwrap_or(&"");
+            let ss_s_output = synrecv_split.first().unwrap_or(&"");
             
             let rest = synrecv_split.get(1).unwrap_or(&"");
             let conntrack_split: Vec<&str> = rest.split("---CONNTRACK---").collect();
-            let synrecv_output = conntrack_split.get(0).unwrap_or(&"");
+            let synrecv_output = conntrack_split.first().unwrap_or(&"");
             
             let rest2 = conntrack_split.get(1).unwrap_or(&"");
             let conntrack_max_split: Vec<&str> = rest2.split("---CONNTRACK_MAX---").collect();
-            let conntrack_current_output = conntrack_max_split.get(0).unwrap_or(&"");
+            let conntrack_current_output = conntrack_max_split.first().unwrap_or(&"");
             
             let rest3 = conntrack_max_split.get(1).unwrap_or(&"");
             let timestamp_split: Vec<&str> = rest3.split("---TIMESTAMP---").collect();
-            let conntrack_max_output = timestamp_split.get(0).unwrap_or(&"");
+            let conntrack_max_output = timestamp_split.first().unwrap_or(&"");
             let timestamp_output = timestamp_split.get(1).unwrap_or(&"");
             
             // Parse ss -s output
@@ -851,11 +1308,11 @@ date +%s%3N 2>/dev/null || echo 0
                     stats.tcp_total = line.split_whitespace().nth(1).and_then(|s| s.parse().ok()).unwrap_or(0);
                     if let Some(estab_start) = line.find("estab ") {
                         let rest = &line[estab_start + 6..];
-                        stats.tcp_established = rest.split(|c| c == ',' || c == ')').next().and_then(|s| s.trim().parse().ok()).unwrap_or(0);
+                        stats.tcp_established = rest.split([',', ')']).next().and_then(|s| s.trim().parse().ok()).unwrap_or(0);
                     }
                     if let Some(tw_start) = line.find("timewait ") {
                         let rest = &line[tw_start + 9..];
-                        stats.tcp_timewait = rest.split(|c| c == ',' || c == ')').next().and_then(|s| s.trim().parse().ok()).unwrap_or(0);
+                        stats.tcp_timewait = rest.split([',', ')']).next().and_then(|s| s.trim().parse().ok()).unwrap_or(0);
                     }
                 } else if line.starts_with("UDP:") {
                     stats.udp_total = line.split_whitespace().nth(1).and_then(|s| s.parse().ok()).unwrap_or(0);
@@ -6,6 +6,7 @@ mod websocket_server;
 use session_manager::SessionManager;
 use websocket_server::WebSocketServer;
 use std::sync::Arc;
+#[cfg(target_os = "macos")]
 use tauri::Manager;
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -21,6 +22,8 @@ pub fn run() {
         .setup({
             let session_manager_clone = session_manager.clone();
             move |app| {
+                session_manager_clone.set_app_handle(app.handle().clone());
+
                 // Set window theme to light mode for white title bar on macOS
                 #[cfg(target_os = "macos")]
                 {
@@ -45,9 +48,21 @@ pub fn run() {
         .manage(session_manager)
         .invoke_handler(tauri::generate_handler![
             commands::ssh_connect,
+            commands::ssh_connect_alias,
+            commands::ssh_config_hosts,
+            commands::ssh_session_details,
+            commands::ssh_add_forward,
+            commands::ssh_remove_forward,
+            commands::ssh_list_forwards,
+            commands::ssh_watch_forward_stats,
             commands::ssh_cancel_connect,
+            commands::ssh_host_key_response,
+            commands::ssh_keyboard_interactive_response,
+            commands::ssh_agent_forward_response,
             commands::ssh_disconnect,
             commands::ssh_execute_command,
+            commands::ssh_execute_command_streaming,
+            commands::ssh_cancel_command,
             commands::ssh_tab_complete,
             commands::get_system_stats,
             commands::list_files,
@@ -1,14 +1,238 @@
-use crate::ssh::{PtySession, SshClient, SshConfig};
+use crate::ssh::{
+    AgentForwardPrompt, CommandControl, ExitStatus, ForwardInfo, HostKeyPrompt, KeyboardInteractivePrompt, OutputStream,
+    PtySession, SshClient, SshConfig, UserPrompt, Utf8Decoder,
+};
 use anyhow::Result;
-use std::collections::HashMap;
-use std::sync::Arc;
-use tokio::sync::RwLock;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+use tokio::sync::{mpsc, oneshot, RwLock};
 use tokio_util::sync::CancellationToken;
 
+/// How often live sessions are checked for a dead connection
+const CONNECTION_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+/// Reconnect backoff: the delay doubles after each failed attempt up to the maximum
+const RECONNECT_INITIAL_DELAY: Duration = Duration::from_secs(1);
+const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(60);
+const RECONNECT_MAX_ATTEMPTS: u32 = 10;
+
+/// How long to wait before reconnect attempt number `attempt` (counting from 1)
+pub(crate) fn reconnect_delay(attempt: u32) -> Duration {
+    let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+    RECONNECT_INITIAL_DELAY.saturating_mul(factor).min(RECONNECT_MAX_DELAY)
+}
+
+/// Event emitted whenever a session's connection state changes
+pub const CONNECTION_STATE_EVENT: &str = "ssh-connection-state";
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "state", rename_all = "lowercase")]
+pub enum ConnectionState {
+    Connected,
+    /// The connection was closed by the user or lost
+    Disconnected { reason: String },
+    /// Waiting `delay_ms` before reconnect attempt number `attempt`
+    Reconnecting { attempt: u32, delay_ms: u64 },
+    /// Connection, port forwards and terminals were re-established
+    Reconnected,
+    /// Reconnecting gave up; the session has been removed
+    Failed { error: String },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnectionStateEvent {
+    pub session_id: String,
+    #[serde(flatten)]
+    pub state: ConnectionState,
+}
+
+/// Event emitted when a server's host key needs the user's confirmation
+pub const HOST_KEY_VERIFICATION_EVENT: &str = "ssh-host-key-verification";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HostKeyVerificationEvent {
+    pub session_id: String,
+    #[serde(flatten)]
+    pub prompt: HostKeyPrompt,
+}
+
+/// Event emitted when the server sends a keyboard-interactive challenge
+pub const KEYBOARD_INTERACTIVE_EVENT: &str = "ssh-keyboard-interactive";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct KeyboardInteractiveEvent {
+    pub session_id: String,
+    #[serde(flatten)]
+    pub prompt: KeyboardInteractivePrompt,
+}
+
+/// Event emitted when the server wants to use the forwarded agent and the user must approve it
+pub const AGENT_FORWARD_EVENT: &str = "ssh-agent-forward-request";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AgentForwardEvent {
+    pub session_id: String,
+    /// Identifies this request in `respond_agent_forward`, as several agent channels may wait at once
+    pub request_id: u64,
+    #[serde(flatten)]
+    pub prompt: AgentForwardPrompt,
+}
+
+/// Event emitted periodically with the traffic counters of a session's port forwards
+pub const FORWARD_STATS_EVENT: &str = "ssh-forward-stats";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ForwardStatsEvent {
+    pub session_id: String,
+    pub forwards: Vec<ForwardInfo>,
+}
+
+/// Event emitted with each chunk of output from a streamed command
+pub const EXEC_OUTPUT_EVENT: &str = "ssh-exec-output";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExecOutputEvent {
+    pub session_id: String,
+    pub execution_id: String,
+    pub stream: OutputStream,
+    pub data: String,
+}
+
+/// Event emitted once a streamed command has ended
+pub const EXEC_EXIT_EVENT: &str = "ssh-exec-exit";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExecExitEvent {
+    pub session_id: String,
+    pub execution_id: String,
+    #[serde(flatten)]
+    pub status: ExitStatus,
+    pub duration_ms: u64,
+    /// The command was stopped through `cancel_execution`
+    pub cancelled: bool,
+}
+
+/// A streamed command that has not ended yet
+struct RunningExecution {
+    /// `None` while the command is still being started; the entry reserves its id
+    control: Option<Arc<CommandControl>>,
+    cancelled: bool,
+    /// Signal of a cancel that arrived while the command was starting
+    pending_signal: Option<String>,
+}
+
+/// Streamed commands that have not ended yet, keyed by session and execution id
+#[derive(Default)]
+pub(crate) struct Executions {
+    running: RwLock<HashMap<(String, String), RunningExecution>>,
+}
+
+impl Executions {
+    /// Claim `execution_id` within the session before its command starts
+    pub(crate) async fn reserve(&self, session_id: &str, execution_id: &str) -> Result<()> {
+        let mut running = self.running.write().await;
+        let key = (session_id.to_string(), execution_id.to_string());
+        if running.contains_key(&key) {
+            return Err(anyhow::anyhow!("Execution {} is already running", execution_id));
+        }
+        running.insert(
+            key,
+            RunningExecution {
+                control: None,
+                cancelled: false,
+                pending_signal: None,
+            },
+        );
+        Ok(())
+    }
+
+    /// Attach the started command, stopping it straight away if it was cancelled while starting
+    pub(crate) async fn started(&self, session_id: &str, execution_id: &str, control: Arc<CommandControl>) -> Result<()> {
+        let cancel = {
+            let mut running = self.running.write().await;
+            let key = (session_id.to_string(), execution_id.to_string());
+            match running.get_mut(&key) {
+                Some(execution) => {
+                    execution.control = Some(control.clone());
+                    execution.cancelled.then(|| execution.pending_signal.take())
+                }
+                None => None,
+            }
+        };
+        match cancel {
+            Some(signal) => control.cancel(signal.as_deref()).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Stop a command by sending it `signal`, or by closing its channel when `None`.
+    /// A command still starting is stopped once it has started.
+    pub(crate) async fn cancel(&self, session_id: &str, execution_id: &str, signal: Option<&str>) -> Result<()> {
+        let control = {
+            let mut running = self.running.write().await;
+            let key = (session_id.to_string(), execution_id.to_string());
+            let execution = running
+                .get_mut(&key)
+                .ok_or_else(|| anyhow::anyhow!("Execution not found"))?;
+            execution.cancelled = true;
+            match &execution.control {
+                Some(control) => control.clone(),
+                None => {
+                    execution.pending_signal = signal.map(str::to_string);
+                    return Ok(());
+                }
+            }
+        };
+        control.cancel(signal).await
+    }
+
+    /// Forget an execution that ended or failed to start. Returns whether it was cancelled.
+    pub(crate) async fn finish(&self, session_id: &str, execution_id: &str) -> bool {
+        let key = (session_id.to_string(), execution_id.to_string());
+        self.running
+            .write()
+            .await
+            .remove(&key)
+            .is_some_and(|execution| execution.cancelled)
+    }
+}
+
+/// Error returned when the user cancels a pending connection
+#[derive(Debug)]
+struct ConnectionCancelled;
+
+impl std::fmt::Display for ConnectionCancelled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Connection cancelled by user")
+    }
+}
+
+impl std::error::Error for ConnectionCancelled {}
+
+/// Reply channels of agent use requests, keyed by session and request id
+type AgentPrompts = HashMap<(String, u64), oneshot::Sender<bool>>;
+
+/// Reply channel of a prompt waiting for the user's answer
+enum PendingPrompt {
+    HostKey(oneshot::Sender<bool>),
+    KeyboardInteractive(oneshot::Sender<Vec<String>>),
+}
+
 pub struct SessionManager {
     sessions: Arc<RwLock<HashMap<String, Arc<RwLock<SshClient>>>>>,
     pub pty_sessions: Arc<RwLock<HashMap<String, Arc<PtySession>>>>,
     pending_connections: Arc<RwLock<HashMap<String, CancellationToken>>>,
+    pending_prompts: Arc<RwLock<HashMap<String, PendingPrompt>>>,
+    agent_prompts: Arc<RwLock<AgentPrompts>>,
+    next_prompt_id: Arc<AtomicU64>,
+    connection_monitors: Arc<RwLock<HashMap<String, tokio::task::JoinHandle<()>>>>,
+    forward_stats_watchers: Arc<RwLock<HashMap<String, tokio::task::JoinHandle<()>>>>,
+    executions: Arc<Executions>,
+    reconnecting: Arc<RwLock<HashSet<String>>>,
+    app_handle: OnceLock<AppHandle>,
 }
 
 impl SessionManager {
@@ -17,33 +241,354 @@ impl SessionManager {
             sessions: Arc::new(RwLock::new(HashMap::new())),
             pty_sessions: Arc::new(RwLock::new(HashMap::new())),
             pending_connections: Arc::new(RwLock::new(HashMap::new())),
+            pending_prompts: Arc::new(RwLock::new(HashMap::new())),
+            agent_prompts: Arc::new(RwLock::new(HashMap::new())),
+            next_prompt_id: Arc::new(AtomicU64::new(1)),
+            connection_monitors: Arc::new(RwLock::new(HashMap::new())),
+            forward_stats_watchers: Arc::new(RwLock::new(HashMap::new())),
+            executions: Arc::default(),
+            reconnecting: Arc::new(RwLock::new(HashSet::new())),
+            app_handle: OnceLock::new(),
         }
     }
 
-    pub async fn create_session(&self, session_id: String, config: SshConfig) -> Result<()> {
+    /// Set the app handle used to emit events to the frontend
+    pub fn set_app_handle(&self, app_handle: AppHandle) {
+        let _ = self.app_handle.set(app_handle);
+    }
+
+    /// Emit a connection state change to the frontend
+    fn emit_state(&self, session_id: &str, state: ConnectionState) {
+        let Some(app_handle) = self.app_handle.get() else {
+            return;
+        };
+        let event = ConnectionStateEvent {
+            session_id: session_id.to_string(),
+            state,
+        };
+        if let Err(e) = app_handle.emit(CONNECTION_STATE_EVENT, event) {
+            tracing::error!("Failed to emit connection state for {}: {}", session_id, e);
+        }
+    }
+
+    pub async fn create_session(self: &Arc<Self>, session_id: String, config: SshConfig) -> Result<()> {
         // Close existing session with same ID if it exists to release resources (like forwarded ports)
         if let Err(e) = self.close_session(&session_id).await {
             tracing::debug!("No existing session to close for {}: {}", session_id, e);
         }
 
-        let mut client = SshClient::new();
-        let cancel_token = self.register_pending_connection(&session_id).await;
+        let client = self.connect_client(&session_id, &config).await?;
+
+        let mut sessions = self.sessions.write().await;
+        sessions.insert(session_id.clone(), Arc::new(RwLock::new(client)));
+        drop(sessions);
+
+        self.emit_state(&session_id, ConnectionState::Connected);
+        self.spawn_connection_monitor(&session_id).await;
+
+        Ok(())
+    }
+
+    /// Connect a new client, forwarding prompts to the frontend and honouring cancellation
+    async fn connect_client(&self, session_id: &str, config: &SshConfig) -> Result<SshClient> {
+        let (prompt_tx, prompt_rx) = mpsc::unbounded_channel();
+        let prompt_task = self.spawn_prompt_forwarder(session_id, prompt_rx);
+
+        let mut client = SshClient::new().with_prompts(prompt_tx);
+        let cancel_token = self.register_pending_connection(session_id).await;
 
         let connect_result = tokio::select! {
-            res = client.connect(&config) => res,
-            _ = cancel_token.cancelled() => Err(anyhow::anyhow!("Connection cancelled by user")),
+            res = client.connect(config) => res,
+            _ = cancel_token.cancelled() => Err(ConnectionCancelled.into()),
         };
 
-        self.clear_pending_connection(&session_id).await;
+        // Agent use confirmations arrive after connecting, so a connected client keeps its
+        // forwarder; it stops once the client and its handler are dropped
+        if connect_result.is_err() {
+            prompt_task.abort();
+        }
+        self.clear_pending_connection(session_id).await;
 
-        connect_result?;
-        
-        let mut sessions = self.sessions.write().await;
-        sessions.insert(session_id, Arc::new(RwLock::new(client)));
-        
+        connect_result.map(|_| client)
+    }
+
+    /// Watch a session for a dead connection and reconnect it when that happens
+    async fn spawn_connection_monitor(self: &Arc<Self>, session_id: &str) {
+        let manager = self.clone();
+        let id = session_id.to_string();
+
+        // Held until the monitor is registered, so it can't finish and clean up before that
+        let mut monitors = self.connection_monitors.write().await;
+        let monitor = tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(CONNECTION_CHECK_INTERVAL).await;
+
+                let Some(client) = manager.get_session(&id).await else {
+                    break;
+                };
+                let (alive, config) = {
+                    let client = client.read().await;
+                    (client.is_connected(), client.config().cloned())
+                };
+                if alive {
+                    continue;
+                }
+
+                tracing::warn!("SSH connection for session {} was lost", id);
+                manager.emit_state(
+                    &id,
+                    ConnectionState::Disconnected {
+                        reason: "Connection lost".to_string(),
+                    },
+                );
+
+                // Free forwarded ports and jump host connections of the dead transport
+                if let Err(e) = client.write().await.disconnect().await {
+                    tracing::debug!("Error cleaning up lost connection {}: {}", id, e);
+                }
+
+                let result = match config {
+                    Some(config) if config.auto_reconnect.unwrap_or(true) => {
+                        manager.reconnect(&id, &client, &config).await
+                    }
+                    _ => Err(anyhow::anyhow!("Connection lost")),
+                };
+
+                if let Err(e) = result {
+                    tracing::error!("Giving up on session {}: {}", id, e);
+                    manager.sessions.write().await.remove(&id);
+                    if let Err(e) = manager.close_pty_session(&id).await {
+                        tracing::debug!("No PTY session to close for {}: {}", id, e);
+                    }
+                    manager.emit_state(&id, ConnectionState::Failed { error: e.to_string() });
+                    break;
+                }
+            }
+
+            // Closing or recreating the session may already have replaced this monitor
+            let mut monitors = manager.connection_monitors.write().await;
+            if monitors.get(&id).is_some_and(|monitor| monitor.id() == tokio::task::id()) {
+                monitors.remove(&id);
+            }
+        });
+
+        if let Some(previous) = monitors.insert(session_id.to_string(), monitor) {
+            previous.abort();
+        }
+    }
+
+    /// Emit the session's forward stats every `interval` until the session closes.
+    /// `None` stops a running watcher.
+    pub async fn watch_forward_stats(self: &Arc<Self>, session_id: &str, interval: Option<Duration>) -> Result<()> {
+        // Held until the new watcher is registered, so concurrent calls can't leave two running
+        let mut watchers = self.forward_stats_watchers.write().await;
+        if let Some(previous) = watchers.remove(session_id) {
+            previous.abort();
+        }
+        let Some(interval) = interval else {
+            return Ok(());
+        };
+        if self.get_session(session_id).await.is_none() {
+            return Err(anyhow::anyhow!("Session not found"));
+        }
+
+        let manager = self.clone();
+        let id = session_id.to_string();
+        let watcher = tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+
+                let Some(client) = manager.get_session(&id).await else {
+                    break;
+                };
+                let Some(app_handle) = manager.app_handle.get() else {
+                    continue;
+                };
+                let event = ForwardStatsEvent {
+                    session_id: id.clone(),
+                    forwards: client.read().await.forwards(),
+                };
+                if let Err(e) = app_handle.emit(FORWARD_STATS_EVENT, event) {
+                    tracing::error!("Failed to emit forward stats for {}: {}", id, e);
+                }
+            }
+
+            // A later call may already have replaced this watcher
+            let mut watchers = manager.forward_stats_watchers.write().await;
+            if watchers.get(&id).is_some_and(|watcher| watcher.id() == tokio::task::id()) {
+                watchers.remove(&id);
+            }
+        });
+
+        watchers.insert(session_id.to_string(), watcher);
         Ok(())
     }
 
+    /// Start `command` and stream its output as events under `execution_id` until it ends.
+    /// Returns the environment variables the server refused to set.
+    pub async fn start_execution(self: &Arc<Self>, session_id: &str, execution_id: &str, command: &str) -> Result<Vec<String>> {
+        self.executions.reserve(session_id, execution_id).await?;
+
+        let started = match self.get_session(session_id).await {
+            Some(client) => client.read().await.start_command(command).await,
+            None => Err(anyhow::anyhow!("Session not found")),
+        };
+        let mut running = match started {
+            Ok(running) => running,
+            Err(e) => {
+                self.executions.finish(session_id, execution_id).await;
+                return Err(e);
+            }
+        };
+        let rejected_env = running.rejected_env.clone();
+
+        if let Err(e) = self.executions.started(session_id, execution_id, running.control()).await {
+            tracing::warn!("Failed to cancel execution {}: {}", execution_id, e);
+        }
+
+        let manager = self.clone();
+        let session_id = session_id.to_string();
+        let execution_id = execution_id.to_string();
+        tokio::spawn(async move {
+            let app_handle = manager.app_handle.get();
+            let emit_output = |stream, data: String| {
+                let Some(app_handle) = app_handle else {
+                    return;
+                };
+                if data.is_empty() {
+                    return;
+                }
+                let event = ExecOutputEvent {
+                    session_id: session_id.clone(),
+                    execution_id: execution_id.clone(),
+                    stream,
+                    data,
+                };
+                if let Err(e) = app_handle.emit(EXEC_OUTPUT_EVENT, event) {
+                    tracing::error!("Failed to emit output of execution {}: {}", execution_id, e);
+                }
+            };
+
+            let mut stdout = Utf8Decoder::default();
+            let mut stderr = Utf8Decoder::default();
+            let status = running
+                .wait(|stream, data| {
+                    let decoder = match stream {
+                        OutputStream::Stdout => &mut stdout,
+                        OutputStream::Stderr => &mut stderr,
+                    };
+                    emit_output(stream, decoder.decode(data));
+                })
+                .await;
+            emit_output(OutputStream::Stdout, stdout.finish());
+            emit_output(OutputStream::Stderr, stderr.finish());
+
+            let cancelled = manager.executions.finish(&session_id, &execution_id).await;
+            let Some(app_handle) = app_handle else {
+                return;
+            };
+            let event = ExecExitEvent {
+                session_id: session_id.clone(),
+                execution_id: execution_id.clone(),
+                status,
+                duration_ms: running.duration_ms(),
+                cancelled,
+            };
+            if let Err(e) = app_handle.emit(EXEC_EXIT_EVENT, event) {
+                tracing::error!("Failed to emit exit of execution {}: {}", execution_id, e);
+            }
+        });
+
+        Ok(rejected_env)
+    }
+
+    /// Stop a streamed command of the session by sending it `signal`, or by closing its channel when `None`
+    pub async fn cancel_execution(&self, session_id: &str, execution_id: &str, signal: Option<&str>) -> Result<()> {
+        self.executions.cancel(session_id, execution_id, signal).await
+    }
+
+    /// Reconnect with exponential backoff using the session's original settings,
+    /// then swap the new connection in and reopen its terminal
+    async fn reconnect(&self, session_id: &str, client: &Arc<RwLock<SshClient>>, config: &SshConfig) -> Result<()> {
+        self.reconnecting.write().await.insert(session_id.to_string());
+
+        let mut last_error = anyhow::anyhow!("Connection lost");
+        let mut reconnected = None;
+
+        for attempt in 1..=RECONNECT_MAX_ATTEMPTS {
+            let delay = reconnect_delay(attempt);
+            self.emit_state(
+                session_id,
+                ConnectionState::Reconnecting {
+                    attempt,
+                    delay_ms: delay.as_millis() as u64,
+                },
+            );
+            tokio::time::sleep(delay).await;
+
+            tracing::info!("Reconnecting session {} (attempt {})", session_id, attempt);
+            match self.connect_client(session_id, config).await {
+                Ok(new_client) => {
+                    reconnected = Some(new_client);
+                    break;
+                }
+                Err(e) if e.is::<ConnectionCancelled>() => {
+                    last_error = e;
+                    break;
+                }
+                Err(e) => {
+                    tracing::warn!("Reconnect attempt {} for {} failed: {}", attempt, session_id, e);
+                    last_error = e;
+                }
+            }
+        }
+
+        let Some(new_client) = reconnected else {
+            self.reconnecting.write().await.remove(session_id);
+            return Err(last_error);
+        };
+
+        *client.write().await = new_client;
+        self.reopen_pty_session(session_id, client).await;
+        self.reconnecting.write().await.remove(session_id);
+
+        tracing::info!("Session {} reconnected", session_id);
+        self.emit_state(session_id, ConnectionState::Reconnected);
+        Ok(())
+    }
+
+    /// Replace a terminal whose channel died with the connection by a fresh one of the same size
+    async fn reopen_pty_session(&self, session_id: &str, client: &Arc<RwLock<SshClient>>) {
+        let Some(old_pty) = self.pty_sessions.read().await.get(session_id).cloned() else {
+            return;
+        };
+        let (cols, rows) = old_pty.get_size().await;
+        old_pty.close().await;
+
+        match client.read().await.create_pty_session(cols, rows).await {
+            Ok(pty) => {
+                self.pty_sessions
+                    .write()
+                    .await
+                    .insert(session_id.to_string(), Arc::new(pty));
+            }
+            Err(e) => tracing::error!("Failed to reopen terminal for {}: {}", session_id, e),
+        }
+    }
+
+    /// Whether a session's terminal is only down because its connection is being restored
+    async fn awaiting_reconnect(&self, session_id: &str) -> bool {
+        if self.reconnecting.read().await.contains(session_id) {
+            return true;
+        }
+        let Some(client) = self.get_session(session_id).await else {
+            return false;
+        };
+        let client = client.read().await;
+        !client.is_connected() && client.config().is_some_and(|c| c.auto_reconnect.unwrap_or(true))
+    }
+
     async fn register_pending_connection(&self, session_id: &str) -> CancellationToken {
         let token = CancellationToken::new();
         let mut pending = self.pending_connections.write().await;
@@ -54,6 +599,119 @@ impl SessionManager {
     async fn clear_pending_connection(&self, session_id: &str) {
         let mut pending = self.pending_connections.write().await;
         pending.remove(session_id);
+        drop(pending);
+
+        self.pending_prompts.write().await.remove(session_id);
+    }
+
+    /// Forward prompts raised by the SSH layer to the frontend as events,
+    /// keeping the reply channel until the user answers
+    fn spawn_prompt_forwarder(
+        &self,
+        session_id: &str,
+        mut prompt_rx: mpsc::UnboundedReceiver<UserPrompt>,
+    ) -> tokio::task::JoinHandle<()> {
+        let session_id = session_id.to_string();
+        let app_handle = self.app_handle.get().cloned();
+        let pending_prompts = self.pending_prompts.clone();
+        let agent_prompts = self.agent_prompts.clone();
+        let next_prompt_id = self.next_prompt_id.clone();
+
+        tokio::spawn(async move {
+            while let Some(prompt) = prompt_rx.recv().await {
+                // Without a frontend the reply channel is dropped, which the SSH layer treats as a refusal
+                let Some(app_handle) = &app_handle else {
+                    tracing::warn!("No frontend available to answer prompt for {}", session_id);
+                    continue;
+                };
+
+                // Register the reply channel before emitting so a fast answer is never lost
+                let emitted = match prompt {
+                    UserPrompt::HostKey { prompt, reply } => {
+                        pending_prompts
+                            .write()
+                            .await
+                            .insert(session_id.clone(), PendingPrompt::HostKey(reply));
+                        let event = HostKeyVerificationEvent {
+                            session_id: session_id.clone(),
+                            prompt,
+                        };
+                        app_handle.emit(HOST_KEY_VERIFICATION_EVENT, event)
+                    }
+                    UserPrompt::KeyboardInteractive { prompt, reply } => {
+                        pending_prompts
+                            .write()
+                            .await
+                            .insert(session_id.clone(), PendingPrompt::KeyboardInteractive(reply));
+                        let event = KeyboardInteractiveEvent {
+                            session_id: session_id.clone(),
+                            prompt,
+                        };
+                        app_handle.emit(KEYBOARD_INTERACTIVE_EVENT, event)
+                    }
+                    UserPrompt::AgentForward { prompt, reply } => {
+                        let request_id = next_prompt_id.fetch_add(1, Ordering::Relaxed);
+                        let mut pending = agent_prompts.write().await;
+                        // Requests that timed out on the SSH side have nobody left to answer
+                        pending.retain(|_, reply| !reply.is_closed());
+                        pending.insert((session_id.clone(), request_id), reply);
+                        drop(pending);
+
+                        let event = AgentForwardEvent {
+                            session_id: session_id.clone(),
+                            request_id,
+                            prompt,
+                        };
+                        let emitted = app_handle.emit(AGENT_FORWARD_EVENT, event);
+                        if let Err(e) = &emitted {
+                            tracing::error!("Failed to emit prompt event for {}: {}", session_id, e);
+                            agent_prompts.write().await.remove(&(session_id.clone(), request_id));
+                        }
+                        continue;
+                    }
+                };
+
+                if let Err(e) = emitted {
+                    tracing::error!("Failed to emit prompt event for {}: {}", session_id, e);
+                    pending_prompts.write().await.remove(&session_id);
+                }
+            }
+        })
+    }
+
+    /// Deliver the user's answer to a pending host key verification
+    pub async fn respond_host_key(&self, session_id: &str, accept: bool) -> bool {
+        let mut pending = self.pending_prompts.write().await;
+        match pending.remove(session_id) {
+            Some(PendingPrompt::HostKey(reply)) => reply.send(accept).is_ok(),
+            Some(other) => {
+                pending.insert(session_id.to_string(), other);
+                false
+            }
+            None => false,
+        }
+    }
+
+    /// Deliver the user's answers to a pending keyboard-interactive challenge
+    pub async fn respond_keyboard_interactive(&self, session_id: &str, responses: Vec<String>) -> bool {
+        let mut pending = self.pending_prompts.write().await;
+        match pending.remove(session_id) {
+            Some(PendingPrompt::KeyboardInteractive(reply)) => reply.send(responses).is_ok(),
+            Some(other) => {
+                pending.insert(session_id.to_string(), other);
+                false
+            }
+            None => false,
+        }
+    }
+
+    /// Deliver the user's decision on a pending use of the forwarded agent
+    pub async fn respond_agent_forward(&self, session_id: &str, request_id: u64, allow: bool) -> bool {
+        let mut pending = self.agent_prompts.write().await;
+        match pending.remove(&(session_id.to_string(), request_id)) {
+            Some(reply) => reply.send(allow).is_ok(),
+            None => false,
+        }
     }
 
     pub async fn cancel_pending_connection(&self, session_id: &str) -> bool {
@@ -72,6 +730,17 @@ impl SessionManager {
     }
 
     pub async fn close_session(&self, session_id: &str) -> Result<()> {
+        // Stop watching the connection so closing it doesn't trigger a reconnect
+        if let Some(monitor) = self.connection_monitors.write().await.remove(session_id) {
+            monitor.abort();
+        }
+        self.reconnecting.write().await.remove(session_id);
+        if let Some(watcher) = self.forward_stats_watchers.write().await.remove(session_id) {
+            watcher.abort();
+        }
+        // Refuses any agent use still waiting for the user
+        self.agent_prompts.write().await.retain(|(id, _), _| id != session_id);
+
         // First close any PTY sessions for this SSH session
         if let Err(e) = self.close_pty_session(session_id).await {
             tracing::debug!("No PTY session to close for {}: {}", session_id, e);
@@ -79,6 +748,13 @@ impl SessionManager {
 
         let mut sessions = self.sessions.write().await;
         if let Some(client) = sessions.remove(session_id) {
+            drop(sessions);
+            self.emit_state(
+                session_id,
+                ConnectionState::Disconnected {
+                    reason: "Closed".to_string(),
+                },
+            );
             let mut client = client.write().await;
             client.disconnect().await?;
         }
@@ -94,12 +770,13 @@ impl SessionManager {
     
     /// Start a PTY shell session (like ttyd does)
     /// Enables interactive commands: vim, less, more, top, htop, etc.
+    /// Returns the environment variables the server refused to set.
     pub async fn start_pty_session(
         &self,
         session_id: &str,
         cols: u32,
         rows: u32,
-    ) -> Result<()> {
+    ) -> Result<Vec<String>> {
         // Get the SSH client
         let sessions = self.sessions.read().await;
         let client = sessions
@@ -111,11 +788,13 @@ impl SessionManager {
         // Create PTY session
         let pty = client.create_pty_session(cols, rows).await?;
         
+        let rejected_env = pty.rejected_env.clone();
+
         // Store PTY session
         let mut pty_sessions = self.pty_sessions.write().await;
         pty_sessions.insert(session_id.to_string(), Arc::new(pty));
         
-        Ok(())
+        Ok(rejected_env)
     }
     
     /// Send data to PTY (user input)
@@ -162,7 +841,16 @@ impl SessionManager {
         
         // Use the enhanced PTY session's safe read method
         // 1ms timeout for ultra-low latency
-        pty.read(1).await
+        let pty = pty.clone();
+        drop(pty_sessions);
+        match pty.read(1).await {
+            // Keep the terminal reader waiting while the connection is restored
+            Err(_) if pty.is_closed() && self.awaiting_reconnect(session_id).await => {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                Ok(Vec::new())
+            }
+            result => result,
+        }
     }
     
     /// Close PTY session with proper cleanup
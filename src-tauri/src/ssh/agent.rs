@@ -0,0 +1,56 @@
+use anyhow::Result;
+use russh::keys::agent::client::AgentClient;
+
+/// Client for the local ssh-agent over the platform's native transport.
+/// A concrete stream type is used because boxed agent streams make the
+/// authentication future fail `Send` checks in Tauri commands.
+#[cfg(unix)]
+pub type LocalAgent = AgentClient<tokio::net::UnixStream>;
+#[cfg(windows)]
+pub type LocalAgent = AgentClient<tokio::net::windows::named_pipe::NamedPipeClient>;
+
+/// Pipe of the Windows OpenSSH Authentication Agent service
+#[cfg(windows)]
+const AGENT_PIPE: &str = r"\\.\pipe\openssh-ssh-agent";
+
+/// Connect to the running ssh-agent (`SSH_AUTH_SOCK` on Unix, the OpenSSH agent pipe on Windows)
+#[cfg(unix)]
+pub async fn connect() -> Result<LocalAgent> {
+    let agent = AgentClient::connect_env().await.map_err(|e| {
+        anyhow::anyhow!(
+            "Failed to connect to SSH agent: {}. Make sure ssh-agent is running and SSH_AUTH_SOCK is set.",
+            e
+        )
+    })?;
+    Ok(agent)
+}
+
+#[cfg(windows)]
+pub async fn connect() -> Result<LocalAgent> {
+    let agent = AgentClient::connect_named_pipe(AGENT_PIPE)
+        .await
+        .map_err(|e| {
+            anyhow::anyhow!(
+                "Failed to connect to SSH agent: {}. Make sure the OpenSSH Authentication Agent service is running.",
+                e
+            )
+        })?;
+    Ok(agent)
+}
+
+/// Raw connection to the running ssh-agent, relayed as is to forwarded agent channels
+#[cfg(unix)]
+pub async fn connect_stream() -> Result<tokio::net::UnixStream> {
+    let path = std::env::var("SSH_AUTH_SOCK")
+        .map_err(|_| anyhow::anyhow!("Cannot forward the SSH agent: SSH_AUTH_SOCK is not set"))?;
+    tokio::net::UnixStream::connect(&path)
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to connect to SSH agent at {}: {}", path, e))
+}
+
+#[cfg(windows)]
+pub async fn connect_stream() -> Result<tokio::net::windows::named_pipe::NamedPipeClient> {
+    tokio::net::windows::named_pipe::ClientOptions::new()
+        .open(AGENT_PIPE)
+        .map_err(|e| anyhow::anyhow!("Failed to connect to SSH agent: {}", e))
+}
@@ -0,0 +1,133 @@
+use anyhow::Result;
+use russh::keys::Algorithm;
+use russh::{cipher, kex, mac, Names, Preferred};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+
+/// Per-connection algorithm preferences, most preferred first.
+/// A list may instead modify russh's defaults OpenSSH-style by prefixing its
+/// first entry: `+` appends, `-` removes and `^` moves entries to the front.
+/// Categories left unset keep the defaults.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AlgorithmPreferences {
+    pub kex: Option<Vec<String>>,
+    pub host_key: Option<Vec<String>>,
+    pub cipher: Option<Vec<String>>,
+    pub mac: Option<Vec<String>>,
+}
+
+/// Algorithms agreed with the server during key exchange
+#[derive(Debug, Clone, Serialize)]
+pub struct NegotiatedAlgorithms {
+    pub kex: String,
+    pub host_key: String,
+    pub cipher: String,
+    pub client_mac: String,
+    pub server_mac: String,
+}
+
+impl From<&Names> for NegotiatedAlgorithms {
+    fn from(names: &Names) -> Self {
+        Self {
+            kex: names.kex.as_ref().to_string(),
+            host_key: names.key.as_str().to_string(),
+            cipher: names.cipher.as_ref().to_string(),
+            client_mac: names.client_mac.as_ref().to_string(),
+            server_mac: names.server_mac.as_ref().to_string(),
+        }
+    }
+}
+
+/// Pseudo-algorithms russh advertises in its kex list to enable protocol
+/// extensions and strict key exchange; kept when the user overrides the list
+const KEX_EXTENSIONS: &[kex::Name] = &[kex::EXTENSION_SUPPORT_AS_CLIENT, kex::EXTENSION_OPENSSH_STRICT_KEX_AS_CLIENT];
+
+/// Map preferences onto russh's `Preferred`, rejecting unknown algorithm names
+pub fn preferred(preferences: &AlgorithmPreferences) -> Result<Preferred> {
+    let defaults = Preferred::default();
+    let mut preferred = defaults.clone();
+
+    if let Some(spec) = &preferences.kex {
+        let mut kex = apply(&defaults.kex, spec, |name| name.as_ref(), |name| kex::Name::try_from(name).ok())
+            .map_err(|name| unsupported("key exchange", &name, kex::ALL_KEX_ALGORITHMS.iter().map(|n| n.as_ref())))?;
+        for extension in KEX_EXTENSIONS {
+            if !kex.contains(extension) {
+                kex.push(*extension);
+            }
+        }
+        preferred.kex = Cow::Owned(kex);
+    }
+
+    if let Some(spec) = &preferences.host_key {
+        let host_key = apply(&defaults.key, spec, Algorithm::as_str, |name| Algorithm::new(name).ok())
+            .map_err(|name| unsupported("host key", &name, defaults.key.iter().map(Algorithm::as_str)))?;
+        preferred.key = Cow::Owned(host_key);
+    }
+
+    if let Some(spec) = &preferences.cipher {
+        let cipher = apply(&defaults.cipher, spec, |name| name.as_ref(), |name| cipher::Name::try_from(name).ok())
+            .map_err(|name| unsupported("cipher", &name, cipher::ALL_CIPHERS.iter().map(|n| n.as_ref())))?;
+        preferred.cipher = Cow::Owned(cipher);
+    }
+
+    if let Some(spec) = &preferences.mac {
+        let mac = apply(&defaults.mac, spec, |name| name.as_ref(), |name| mac::Name::try_from(name).ok())
+            .map_err(|name| unsupported("MAC", &name, mac::ALL_MAC_ALGORITHMS.iter().map(|n| n.as_ref())))?;
+        preferred.mac = Cow::Owned(mac);
+    }
+
+    Ok(preferred)
+}
+
+/// Build one algorithm list from the defaults and a user spec.
+/// Returns the first name that can't be parsed as the error.
+fn apply<T: Clone>(
+    defaults: &[T],
+    spec: &[String],
+    name_of: impl Fn(&T) -> &str,
+    parse: impl Fn(&str) -> Option<T>,
+) -> std::result::Result<Vec<T>, String> {
+    let first = spec.first().map(String::as_str).unwrap_or_default();
+    let (mode, names): (Option<char>, Vec<&str>) = match first.chars().next() {
+        Some(prefix @ ('+' | '-' | '^')) => (
+            Some(prefix),
+            std::iter::once(&first[1..]).chain(spec[1..].iter().map(String::as_str)).collect(),
+        ),
+        _ => (None, spec.iter().map(String::as_str).collect()),
+    };
+    let names: Vec<&str> = names.into_iter().map(str::trim).filter(|n| !n.is_empty()).collect();
+
+    // Removal works on names, so algorithms unknown to russh can be listed harmlessly
+    if mode == Some('-') {
+        return Ok(defaults.iter().filter(|a| !names.contains(&name_of(a))).cloned().collect());
+    }
+
+    let mut parsed = Vec::with_capacity(names.len());
+    for name in names {
+        parsed.push(parse(name).ok_or_else(|| name.to_string())?);
+    }
+
+    let is_parsed = |a: &T| parsed.iter().any(|p| name_of(p) == name_of(a));
+    Ok(match mode {
+        Some('+') => {
+            let mut list = defaults.to_vec();
+            list.extend(parsed.iter().filter(|p| !defaults.iter().any(|a| name_of(a) == name_of(p))).cloned());
+            list
+        }
+        Some('^') => {
+            let rest: Vec<T> = defaults.iter().filter(|a| !is_parsed(a)).cloned().collect();
+            parsed.iter().cloned().chain(rest).collect()
+        }
+        _ => parsed,
+    })
+}
+
+fn unsupported<'a>(kind: &str, name: &str, supported: impl Iterator<Item = &'a str>) -> anyhow::Error {
+    let supported: Vec<&str> = supported.filter(|n| !n.contains("ext-info") && !n.contains("kex-strict")).collect();
+    anyhow::anyhow!(
+        "Unsupported {} algorithm '{}'. Supported: {}",
+        kind,
+        name,
+        supported.join(", ")
+    )
+}
@@ -0,0 +1,78 @@
+use anyhow::Result;
+use russh::keys::ssh_key::certificate::CertType;
+use russh::keys::{Certificate, PrivateKey, PublicKey};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Locate the certificate for a private key: the explicit path if given,
+/// otherwise `<key>-cert.pub` next to the key file, as OpenSSH does
+pub fn find(key_path: &Path, cert_path: Option<&Path>) -> Option<PathBuf> {
+    if let Some(cert_path) = cert_path {
+        return Some(cert_path.to_path_buf());
+    }
+
+    let mut candidate = key_path.as_os_str().to_owned();
+    candidate.push("-cert.pub");
+    let candidate = PathBuf::from(candidate);
+    candidate.exists().then_some(candidate)
+}
+
+/// Load a user certificate and make sure it can be used with `private_key` right now
+pub fn load(path: &Path, private_key: &PrivateKey) -> Result<Certificate> {
+    let cert = Certificate::read_file(path).map_err(|e| {
+        anyhow::anyhow!(
+            "Failed to load SSH certificate from {}: {}. Ensure the file is a valid OpenSSH certificate.",
+            path.display(),
+            e
+        )
+    })?;
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    check(&cert, private_key.public_key(), now)
+        .map_err(|e| anyhow::anyhow!("SSH certificate {} cannot be used: {}", path.display(), e))?;
+
+    Ok(cert)
+}
+
+/// Check that a certificate is a user certificate for `key` and valid at `now` (unix seconds)
+pub fn check(cert: &Certificate, key: &PublicKey, now: u64) -> Result<()> {
+    if cert.cert_type() != CertType::User {
+        return Err(anyhow::anyhow!("it is a host certificate, not a user certificate"));
+    }
+
+    if cert.public_key() != key.key_data() {
+        return Err(anyhow::anyhow!("it was issued for a different key than the private key provided"));
+    }
+
+    if now < cert.valid_after() {
+        return Err(anyhow::anyhow!(
+            "it is not valid yet (becomes valid in {})",
+            describe_duration(cert.valid_after() - now)
+        ));
+    }
+
+    if now >= cert.valid_before() {
+        return Err(anyhow::anyhow!(
+            "it expired {} ago. Request a new certificate and try again.",
+            describe_duration(now - cert.valid_before())
+        ));
+    }
+
+    Ok(())
+}
+
+fn describe_duration(secs: u64) -> String {
+    let (days, hours, minutes) = (secs / 86_400, (secs % 86_400) / 3_600, (secs % 3_600) / 60);
+    if days > 0 {
+        format!("{}d {}h", days, hours)
+    } else if hours > 0 {
+        format!("{}h {}m", hours, minutes)
+    } else if minutes > 0 {
+        format!("{}m", minutes)
+    } else {
+        format!("{}s", secs)
+    }
+}
@@ -0,0 +1,101 @@
+use anyhow::Result;
+use russh::{client, Channel, ChannelMsg};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::time::{timeout, Duration};
+
+use super::x11::{self, X11Forwarding};
+
+/// How long the server gets to answer a channel request
+const REPLY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Requests made on a session channel before its shell or command starts
+#[derive(Default, Clone)]
+pub struct ChannelSetup {
+    /// Variables sent with `env` requests, in this order
+    pub env: Vec<(String, String)>,
+    pub agent_forwarding: bool,
+    /// Only requested for terminals
+    pub x11: Option<Arc<X11Forwarding>>,
+}
+
+impl ChannelSetup {
+    /// Send the requests for a terminal or command channel. Returns the names of the
+    /// variables the server refused, which it only accepts when listed in its AcceptEnv.
+    pub async fn apply(&self, channel: &mut Channel<client::Msg>, terminal: bool) -> Result<Vec<String>> {
+        // X11 forwarding is optional: a refusal only loses the display, not the shell
+        if let (Some(x11), true) = (&self.x11, terminal) {
+            timeout(
+                REPLY_TIMEOUT,
+                channel.request_x11(true, false, x11::AUTH_PROTOCOL, x11.fake_cookie_hex(), x11.screen()),
+            )
+            .await
+            .map_err(|_| anyhow::anyhow!("Timeout requesting X11 forwarding"))?
+            .map_err(|e| anyhow::anyhow!("Failed to request X11 forwarding: {}", e))?;
+            if !request_accepted(channel).await? {
+                tracing::warn!("Server refused X11 forwarding");
+            }
+        }
+
+        // Like OpenSSH, no reply is requested; a refusal only shows up when the agent is used
+        if self.agent_forwarding {
+            channel
+                .agent_forward(false)
+                .await
+                .map_err(|e| anyhow::anyhow!("Failed to request agent forwarding: {}", e))?;
+        }
+
+        let mut rejected = Vec::new();
+        for (name, value) in &self.env {
+            channel
+                .set_env(true, name.as_str(), value.as_str())
+                .await
+                .map_err(|e| anyhow::anyhow!("Failed to send environment variable {}: {}", name, e))?;
+            if !request_accepted(channel).await? {
+                rejected.push(name.clone());
+            }
+        }
+        if !rejected.is_empty() {
+            tracing::warn!(
+                "Server rejected environment variables {}; allow them with AcceptEnv in sshd_config",
+                rejected.join(", ")
+            );
+        }
+        Ok(rejected)
+    }
+}
+
+/// Variables to send on each channel: the local locale unless disabled, overridden by `env`
+pub fn environment(env: Option<&HashMap<String, String>>, forward_locale: bool) -> Vec<(String, String)> {
+    let mut variables: Vec<(String, String)> = Vec::new();
+    if forward_locale {
+        variables.extend(std::env::vars().filter(|(name, _)| is_locale_variable(name)));
+    }
+    for (name, value) in env.into_iter().flatten() {
+        variables.retain(|(existing, _)| existing != name);
+        variables.push((name.clone(), value.clone()));
+    }
+    variables.sort();
+    variables
+}
+
+/// `LANG` and the `LC_*` categories, which OpenSSH clients send by default
+pub fn is_locale_variable(name: &str) -> bool {
+    name == "LANG" || name.starts_with("LC_")
+}
+
+/// Wait for the server's answer to the last channel request sent with `want_reply`
+pub async fn request_accepted(channel: &mut Channel<client::Msg>) -> Result<bool> {
+    timeout(REPLY_TIMEOUT, async {
+        loop {
+            match channel.wait().await {
+                Some(ChannelMsg::Success) => return Ok(true),
+                Some(ChannelMsg::Failure) => return Ok(false),
+                Some(_) => {}
+                None => return Err(anyhow::anyhow!("Channel closed while waiting for the server's reply")),
+            }
+        }
+    })
+    .await
+    .map_err(|_| anyhow::anyhow!("Timeout waiting for the server's reply"))?
+}
@@ -0,0 +1,240 @@
+use anyhow::Result;
+use russh::{client, Channel, ChannelMsg, ChannelReadHalf, ChannelWriteHalf, Sig};
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::Instant;
+
+/// SSH extended data type of stderr
+const STDERR: u32 = 1;
+
+/// Which output of a command a chunk came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+/// Signal that terminated a remote command
+#[derive(Debug, Clone, Serialize)]
+pub struct ExitSignal {
+    /// Signal name without the `SIG` prefix, e.g. `TERM`
+    pub signal: String,
+    pub core_dumped: bool,
+    pub message: String,
+}
+
+/// How a remote command ended
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ExitStatus {
+    /// Missing when the command was killed by a signal or the server sent no status
+    pub exit_code: Option<u32>,
+    pub exit_signal: Option<ExitSignal>,
+}
+
+impl ExitStatus {
+    /// Why the command counts as failed, or `None` if it exited with 0
+    pub fn failure(&self) -> Option<String> {
+        match (&self.exit_code, &self.exit_signal) {
+            (Some(0), _) => None,
+            (Some(code), _) => Some(format!("Command exited with code {}", code)),
+            (None, Some(signal)) => Some(format!("Command was killed by signal {}", signal.signal)),
+            (None, None) => Some("Command ended without reporting an exit status".to_string()),
+        }
+    }
+}
+
+/// Everything a finished remote command produced
+#[derive(Debug, Clone, Serialize)]
+pub struct ExecResult {
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+    #[serde(flatten)]
+    pub status: ExitStatus,
+    pub duration_ms: u64,
+    /// Environment variables the server refused to set
+    pub rejected_env: Vec<String>,
+}
+
+impl ExecResult {
+    /// Failure reason with the command's stderr appended, for callers that only report errors
+    pub fn error_message(&self) -> Option<String> {
+        let failure = self.status.failure()?;
+        let stderr = String::from_utf8_lossy(&self.stderr);
+        let stderr = stderr.trim();
+        Some(if stderr.is_empty() { failure } else { format!("{}: {}", failure, stderr) })
+    }
+}
+
+/// A command running on its own channel
+pub struct RunningCommand {
+    output: ChannelReadHalf,
+    control: Arc<CommandControl>,
+    started: Instant,
+    /// Environment variables the server refused to set
+    pub rejected_env: Vec<String>,
+}
+
+impl RunningCommand {
+    /// Wrap a channel the command was just started on
+    pub fn new(channel: Channel<client::Msg>, started: Instant, rejected_env: Vec<String>) -> Self {
+        let (output, input) = channel.split();
+        Self {
+            output,
+            control: Arc::new(CommandControl { input }),
+            started,
+            rejected_env,
+        }
+    }
+
+    /// Handle for stopping the command while its output is being read
+    pub fn control(&self) -> Arc<CommandControl> {
+        self.control.clone()
+    }
+
+    pub fn duration_ms(&self) -> u64 {
+        self.started.elapsed().as_millis() as u64
+    }
+
+    /// Read the channel until the command ends, handing output to `on_output` as it arrives
+    pub async fn wait(&mut self, mut on_output: impl FnMut(OutputStream, &[u8])) -> ExitStatus {
+        let mut status = ExitStatus::default();
+        let mut eof_received = false;
+
+        while let Some(msg) = self.output.wait().await {
+            match msg {
+                ChannelMsg::Data { ref data } => on_output(OutputStream::Stdout, data),
+                ChannelMsg::ExtendedData { ref data, ext } if ext == STDERR => on_output(OutputStream::Stderr, data),
+                ChannelMsg::ExitStatus { exit_status } => {
+                    status.exit_code = Some(exit_status);
+                    if eof_received {
+                        break;
+                    }
+                }
+                ChannelMsg::ExitSignal { signal_name, core_dumped, error_message, .. } => {
+                    status.exit_signal = Some(ExitSignal {
+                        signal: signal_name_of(&signal_name),
+                        core_dumped,
+                        message: error_message,
+                    });
+                    if eof_received {
+                        break;
+                    }
+                }
+                ChannelMsg::Eof => {
+                    eof_received = true;
+                    if status.exit_code.is_some() || status.exit_signal.is_some() {
+                        break;
+                    }
+                }
+                ChannelMsg::Close => break,
+                _ => {}
+            }
+        }
+        status
+    }
+}
+
+/// Controls a running command from outside the task reading its output
+pub struct CommandControl {
+    input: ChannelWriteHalf<client::Msg>,
+}
+
+impl CommandControl {
+    /// Signal end of input; nothing is written to a command's stdin
+    pub async fn close_input(&self) -> Result<()> {
+        self.input
+            .eof()
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to close the command's input: {}", e))
+    }
+
+    /// Send `signal` (e.g. `INT` or `SIGTERM`) to the command, or close its channel when `None`.
+    /// Servers may ignore signals, in which case closing the channel is the fallback.
+    pub async fn cancel(&self, signal: Option<&str>) -> Result<()> {
+        match signal {
+            Some(name) => self
+                .input
+                .signal(signal_from_name(name)?)
+                .await
+                .map_err(|e| anyhow::anyhow!("Failed to send signal {}: {}", name, e)),
+            None => self
+                .input
+                .close()
+                .await
+                .map_err(|e| anyhow::anyhow!("Failed to close the command's channel: {}", e)),
+        }
+    }
+}
+
+/// Parse a signal name with or without the `SIG` prefix
+pub fn signal_from_name(name: &str) -> Result<Sig> {
+    let name = name.trim().to_ascii_uppercase();
+    let name = name.strip_prefix("SIG").unwrap_or(&name);
+    Ok(match name {
+        "ABRT" => Sig::ABRT,
+        "ALRM" => Sig::ALRM,
+        "FPE" => Sig::FPE,
+        "HUP" => Sig::HUP,
+        "ILL" => Sig::ILL,
+        "INT" => Sig::INT,
+        "KILL" => Sig::KILL,
+        "PIPE" => Sig::PIPE,
+        "QUIT" => Sig::QUIT,
+        "SEGV" => Sig::SEGV,
+        "TERM" => Sig::TERM,
+        "USR1" => Sig::USR1,
+        "" => return Err(anyhow::anyhow!("Signal name is empty")),
+        other if other.chars().all(|c| c.is_ascii_alphanumeric()) => Sig::Custom(other.to_string()),
+        other => return Err(anyhow::anyhow!("Invalid signal name '{}'", other)),
+    })
+}
+
+/// Decodes streamed output as UTF-8, holding back a character split across chunks
+#[derive(Default)]
+pub struct Utf8Decoder {
+    pending: Vec<u8>,
+}
+
+impl Utf8Decoder {
+    /// Text of `data` and any bytes held back before it; invalid bytes become U+FFFD
+    pub fn decode(&mut self, data: &[u8]) -> String {
+        self.pending.extend_from_slice(data);
+        let complete = self.pending.len() - incomplete_tail(&self.pending);
+        let text = String::from_utf8_lossy(&self.pending[..complete]).into_owned();
+        self.pending.drain(..complete);
+        text
+    }
+
+    /// Whatever is still held back once the output has ended
+    pub fn finish(&mut self) -> String {
+        String::from_utf8_lossy(&std::mem::take(&mut self.pending)).into_owned()
+    }
+}
+
+/// Number of trailing bytes that start a character but don't finish it
+fn incomplete_tail(bytes: &[u8]) -> usize {
+    // A character is at most 4 bytes, so only the last 3 can belong to an unfinished one
+    for back in 1..=bytes.len().min(3) {
+        let byte = bytes[bytes.len() - back];
+        if byte & 0xC0 == 0x80 {
+            continue;
+        }
+        let len = match byte {
+            0xC0..=0xDF => 2,
+            0xE0..=0xEF => 3,
+            0xF0..=0xF7 => 4,
+            _ => 1,
+        };
+        return if len > back { back } else { 0 };
+    }
+    0
+}
+
+fn signal_name_of(signal: &Sig) -> String {
+    match signal {
+        Sig::Custom(name) => name.clone(),
+        // The named variants print as the bare signal name
+        other => format!("{:?}", other),
+    }
+}
@@ -0,0 +1,174 @@
+use super::{DynamicForwardPort, ForwardPort, RemoteForwardPort, StreamLocalForwardPort};
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A port forward of any kind
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum ForwardSpec {
+    Local(ForwardPort),
+    Remote(RemoteForwardPort),
+    Dynamic(DynamicForwardPort),
+    StreamLocal(StreamLocalForwardPort),
+}
+
+/// Whether a forward is accepting connections
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "state", rename_all = "lowercase")]
+pub enum ForwardStatus {
+    Listening,
+    /// The listener could not be set up
+    Failed { error: String },
+}
+
+/// A forward on a live session, as reported to the frontend
+#[derive(Debug, Clone, Serialize)]
+pub struct ForwardInfo {
+    pub id: u64,
+    pub forward: ForwardSpec,
+    pub status: ForwardStatus,
+    pub active_connections: usize,
+    pub total_connections: u64,
+    /// Bytes sent to the server through this forward
+    pub bytes_sent: u64,
+    /// Bytes received from the server through this forward
+    pub bytes_received: u64,
+    /// Unix time in milliseconds of the last connection or transfer
+    pub last_activity: Option<u64>,
+    /// Set when the forward listens beyond loopback, so other machines can use it
+    pub warning: Option<String>,
+}
+
+/// Bookkeeping for one forward of a connected client
+pub(super) struct ActiveForward {
+    pub id: u64,
+    pub spec: ForwardSpec,
+    pub status: ForwardStatus,
+    pub stats: Arc<ForwardStats>,
+    /// Accept loop of a local, dynamic or socket forward
+    pub task: Option<tokio::task::JoinHandle<()>>,
+    /// Port the server actually listens on, for remote forwards
+    pub bound_port: Option<u32>,
+}
+
+impl ActiveForward {
+    pub fn info(&self) -> ForwardInfo {
+        ForwardInfo {
+            id: self.id,
+            forward: self.spec.clone(),
+            status: self.status.clone(),
+            active_connections: self.stats.active_connections.load(Ordering::Relaxed),
+            total_connections: self.stats.total_connections.load(Ordering::Relaxed),
+            bytes_sent: self.stats.bytes_sent.load(Ordering::Relaxed),
+            bytes_received: self.stats.bytes_received.load(Ordering::Relaxed),
+            last_activity: self.stats.last_activity(),
+            warning: exposure_warning(&self.spec),
+        }
+    }
+
+    /// Stop accepting connections and remove the Unix socket a socket forward created
+    pub fn stop(&self) {
+        if let Some(task) = &self.task {
+            task.abort();
+        }
+        // A socket that failed to bind belongs to someone else
+        if let (ForwardSpec::StreamLocal(forward), ForwardStatus::Listening) = (&self.spec, &self.status) {
+            if let Some(path) = &forward.local_socket_path {
+                if let Err(e) = std::fs::remove_file(super::expand_home(path)) {
+                    tracing::debug!("Failed to remove forward socket {}: {}", path, e);
+                }
+            }
+        }
+    }
+}
+
+/// Warn about local forwards that accept connections from the network
+fn exposure_warning(spec: &ForwardSpec) -> Option<String> {
+    let (bind_address, target) = match spec {
+        ForwardSpec::Local(forward) => (
+            forward.bind_address.as_deref()?,
+            format!("{}:{}", forward.remote_host, forward.remote_port),
+        ),
+        ForwardSpec::Dynamic(forward) => (
+            forward.bind_address.as_deref()?,
+            "any host the server can reach".to_string(),
+        ),
+        // A local socket path takes precedence over the TCP listener
+        ForwardSpec::StreamLocal(forward) if forward.local_socket_path.is_none() => {
+            (forward.bind_address.as_deref()?, forward.remote_socket_path.clone())
+        }
+        _ => return None,
+    };
+    (!is_loopback(bind_address)).then(|| {
+        format!(
+            "Listening on {}, so other machines can reach {} through this forward",
+            bind_address, target
+        )
+    })
+}
+
+fn is_loopback(address: &str) -> bool {
+    address.eq_ignore_ascii_case("localhost") || address.parse::<IpAddr>().is_ok_and(|ip| ip.is_loopback())
+}
+
+/// Where the handler sends connections arriving on a remote forward
+#[derive(Clone)]
+pub(super) struct RemoteTarget {
+    pub forward: RemoteForwardPort,
+    pub stats: Arc<ForwardStats>,
+}
+
+/// Traffic counters shared by all connections of a forward
+#[derive(Debug, Default)]
+pub(super) struct ForwardStats {
+    pub active_connections: AtomicUsize,
+    pub total_connections: AtomicU64,
+    pub bytes_sent: AtomicU64,
+    pub bytes_received: AtomicU64,
+    /// Unix time in milliseconds, 0 until the first connection
+    last_activity_ms: AtomicU64,
+}
+
+impl ForwardStats {
+    pub fn sent(&self, bytes: usize) {
+        self.bytes_sent.fetch_add(bytes as u64, Ordering::Relaxed);
+        self.touch();
+    }
+
+    pub fn received(&self, bytes: usize) {
+        self.bytes_received.fetch_add(bytes as u64, Ordering::Relaxed);
+        self.touch();
+    }
+
+    pub fn last_activity(&self) -> Option<u64> {
+        Some(self.last_activity_ms.load(Ordering::Relaxed)).filter(|&ms| ms > 0)
+    }
+
+    fn touch(&self) {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_millis() as u64);
+        self.last_activity_ms.store(now, Ordering::Relaxed);
+    }
+}
+
+/// Counts a forwarded connection as active for as long as it is held
+pub(super) struct ConnectionGuard(Arc<ForwardStats>);
+
+impl ConnectionGuard {
+    pub fn new(stats: &Arc<ForwardStats>) -> Self {
+        stats.active_connections.fetch_add(1, Ordering::Relaxed);
+        stats.total_connections.fetch_add(1, Ordering::Relaxed);
+        stats.touch();
+        Self(stats.clone())
+    }
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.0.active_connections.fetch_sub(1, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,217 @@
+use anyhow::Result;
+use hmac::{Hmac, Mac};
+use russh::keys::ssh_key::known_hosts::{Entry, HostPatterns, Marker};
+use russh::keys::ssh_key::rand_core::{OsRng, RngCore};
+use russh::keys::PublicKey;
+use sha1::Sha1;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Result of looking up a server's host key in a known_hosts file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HostKeyStatus {
+    /// A matching entry with the same key exists
+    Known,
+    /// No entry for this host and key type exists yet
+    Unknown,
+    /// An entry for this host and key type exists but holds a different key.
+    /// `lines` are the 1-based line numbers of the conflicting entries.
+    Changed { lines: Vec<usize> },
+    /// The key is explicitly marked `@revoked`
+    Revoked,
+}
+
+/// Default known_hosts location (`~/.ssh/known_hosts`)
+pub fn default_path() -> Option<PathBuf> {
+    let home = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE"))?;
+    Some(PathBuf::from(home).join(".ssh").join("known_hosts"))
+}
+
+/// Name used for a host in known_hosts: `host` for port 22, `[host]:port` otherwise
+pub fn host_entry_name(host: &str, port: u16) -> String {
+    if port == 22 {
+        host.to_string()
+    } else {
+        format!("[{}]:{}", host, port)
+    }
+}
+
+/// Check a server key against the known_hosts file at `path`.
+/// A missing file is treated as empty; malformed lines are skipped.
+pub fn check(path: &Path, host: &str, port: u16, key: &PublicKey) -> Result<HostKeyStatus> {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(c) => c,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(HostKeyStatus::Unknown),
+        Err(e) => return Err(anyhow::anyhow!("Failed to read {}: {}", path.display(), e)),
+    };
+    Ok(check_str(&contents, host, port, key))
+}
+
+/// Check a server key against known_hosts contents
+pub fn check_str(contents: &str, host: &str, port: u16, key: &PublicKey) -> HostKeyStatus {
+    let name = host_entry_name(host, port);
+    let mut known = false;
+    let mut changed_lines = Vec::new();
+
+    for (index, line) in contents.lines().enumerate() {
+        let Some(entry) = parse_line(line) else {
+            continue;
+        };
+        if !host_patterns_match(entry.host_patterns(), &name) {
+            continue;
+        }
+
+        let same_key = entry.public_key().key_data() == key.key_data();
+        match entry.marker() {
+            Some(Marker::Revoked) if same_key => return HostKeyStatus::Revoked,
+            Some(_) => continue,
+            None => {}
+        }
+
+        if same_key {
+            known = true;
+        } else if entry.public_key().algorithm() == key.algorithm() {
+            changed_lines.push(index + 1);
+        }
+    }
+
+    if known {
+        HostKeyStatus::Known
+    } else if !changed_lines.is_empty() {
+        HostKeyStatus::Changed { lines: changed_lines }
+    } else {
+        HostKeyStatus::Unknown
+    }
+}
+
+/// Append a host key to the known_hosts file, creating it if needed.
+/// The host name is hashed when the file already holds hashed entries.
+pub fn learn(path: &Path, host: &str, port: u16, key: &PublicKey) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let existing = std::fs::read_to_string(path).unwrap_or_default();
+    let mut file = std::fs::OpenOptions::new().create(true).append(true).open(path)?;
+
+    if !existing.is_empty() && !existing.ends_with('\n') {
+        file.write_all(b"\n")?;
+    }
+
+    let name = host_entry_name(host, port);
+    let hashed = existing
+        .lines()
+        .filter_map(parse_line)
+        .any(|entry| matches!(entry.host_patterns(), HostPatterns::HashedName { .. }));
+    let name = if hashed { hash_name(&name)?.to_string() } else { name };
+
+    // Write the key without its comment, like ssh does
+    let mut key = key.clone();
+    key.set_comment("");
+    writeln!(file, "{} {}", name, key.to_openssh()?.trim_end())?;
+    Ok(())
+}
+
+/// Remove the given 1-based lines from the known_hosts file, used to drop stale entries
+/// before recording a replaced host key. Only lines naming this host alone are removed;
+/// lines shared with other hosts or patterns are kept, and the new entry takes precedence.
+pub fn remove_lines(path: &Path, host: &str, port: u16, lines: &[usize]) -> Result<()> {
+    let name = host_entry_name(host, port);
+    let contents = std::fs::read_to_string(path)?;
+    let mut kept: String = contents
+        .lines()
+        .enumerate()
+        .filter(|(index, line)| !(lines.contains(&(index + 1)) && names_only(line, &name)))
+        .map(|(_, line)| format!("{}\n", line))
+        .collect();
+
+    if kept.trim().is_empty() {
+        kept.clear();
+    }
+    std::fs::write(path, kept)?;
+    Ok(())
+}
+
+fn parse_line(line: &str) -> Option<Entry> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+    line.parse::<Entry>().ok()
+}
+
+/// Whether a line records a key for `name` and no other host
+fn names_only(line: &str, name: &str) -> bool {
+    let Some(entry) = parse_line(line) else {
+        return false;
+    };
+    match entry.host_patterns() {
+        HostPatterns::HashedName { .. } => host_patterns_match(entry.host_patterns(), name),
+        HostPatterns::Patterns(patterns) => patterns.len() == 1 && patterns[0].eq_ignore_ascii_case(name),
+    }
+}
+
+/// Hash a host name with a random salt, as ssh does with `HashKnownHosts`
+fn hash_name(name: &str) -> Result<HostPatterns> {
+    let mut salt = vec![0u8; 20];
+    OsRng.fill_bytes(&mut salt);
+    let mut mac = Hmac::<Sha1>::new_from_slice(&salt).map_err(|e| anyhow::anyhow!("Failed to hash host name: {}", e))?;
+    mac.update(name.as_bytes());
+    Ok(HostPatterns::HashedName {
+        salt,
+        hash: mac.finalize().into_bytes().into(),
+    })
+}
+
+fn host_patterns_match(patterns: &HostPatterns, name: &str) -> bool {
+    match patterns {
+        HostPatterns::HashedName { salt, hash } => {
+            let Ok(mut mac) = Hmac::<Sha1>::new_from_slice(salt) else {
+                return false;
+            };
+            mac.update(name.as_bytes());
+            mac.verify_slice(hash).is_ok()
+        }
+        HostPatterns::Patterns(patterns) => {
+            let mut matched = false;
+            for pattern in patterns {
+                if let Some(negated) = pattern.strip_prefix('!') {
+                    if wildcard_match(negated, name) {
+                        return false;
+                    }
+                } else if wildcard_match(pattern, name) {
+                    matched = true;
+                }
+            }
+            matched
+        }
+    }
+}
+
+/// Case-insensitive glob match supporting `*` and `?`, as used by
+/// known_hosts and ssh_config host patterns
+pub fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+
+    let (mut p, mut t) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = star {
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
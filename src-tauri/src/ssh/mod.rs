@@ -3,21 +3,124 @@ use russh::*;
 use russh::keys::{self, PublicKey, PrivateKeyWithHashAlg};
 use russh_sftp::client::SftpSession;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::sync::Arc;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 use tokio::net::TcpListener;
+use tokio::sync::{mpsc, oneshot, watch, RwLock};
 
 // Enhanced PTY session module
 mod pty_session;
+// known_hosts lookup and trust-on-first-use recording
+mod known_hosts;
+// Local ssh-agent access
+mod agent;
+// OpenSSH user certificate lookup and validation
+mod certificate;
+// Alternative transports (ProxyCommand, SOCKS5 and HTTP CONNECT proxies)
+mod proxy;
+// ~/.ssh/config parsing
+pub mod ssh_config;
+// Key exchange, cipher, MAC and host key algorithm preferences
+mod algorithms;
+// Dual-stack TCP connection setup
+mod net;
+// Local SOCKS4/4a/5 server for dynamic forwarding
+mod socks;
+// Bookkeeping for the port forwards of a live session
+mod forwards;
+// X11 display forwarding for terminals
+mod x11;
+// Environment, agent and X11 requests made before a shell or command starts
+mod channel_setup;
+// Remote command execution and exit status reporting
+mod exec;
 
+pub use algorithms::{AlgorithmPreferences, NegotiatedAlgorithms};
+pub use forwards::{ForwardInfo, ForwardSpec, ForwardStatus};
+pub use exec::{CommandControl, ExecResult, ExitSignal, ExitStatus, OutputStream, RunningCommand, Utf8Decoder};
+use forwards::{ActiveForward, ConnectionGuard, ForwardStats, RemoteTarget};
+use x11::X11Forwarding;
+use channel_setup::ChannelSetup;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ForwardPort {
+    /// Local address to listen on (default `127.0.0.1`). Use `::1` for IPv6 loopback,
+    /// or `0.0.0.0` (also written `*`) to share the forward with other machines.
+    pub bind_address: Option<String>,
     pub local_port: u16,
     pub remote_host: String,
     pub remote_port: u16,
 }
 
+impl ForwardPort {
+    fn bind_address(&self) -> &str {
+        local_bind_address(self.bind_address.as_deref())
+    }
+}
+
+/// Address a local listener binds to: loopback unless configured otherwise, and every
+/// interface for `*` or an empty address, as in OpenSSH
+fn local_bind_address(bind_address: Option<&str>) -> &str {
+    match bind_address {
+        None => "127.0.0.1",
+        Some("" | "*") => "0.0.0.0",
+        Some(address) => address,
+    }
+}
+
+/// Local SOCKS proxy whose CONNECT requests are tunnelled through the server (like `ssh -D`)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DynamicForwardPort {
+    /// Local address to listen on (default `127.0.0.1`). Anything beyond loopback
+    /// lets other machines use the proxy.
+    pub bind_address: Option<String>,
+    pub local_port: u16,
+}
+
+impl DynamicForwardPort {
+    fn bind_address(&self) -> &str {
+        local_bind_address(self.bind_address.as_deref())
+    }
+}
+
+/// Local TCP port or Unix socket bridged to a Unix socket on the server, e.g. a remote
+/// Docker or Postgres socket (OpenSSH's `LocalForward` with a socket path)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StreamLocalForwardPort {
+    /// Local address to listen on when `local_port` is used (default `127.0.0.1`)
+    pub bind_address: Option<String>,
+    /// Local TCP port to listen on
+    pub local_port: Option<u16>,
+    /// Local Unix socket to create instead of a TCP port. It must not exist yet.
+    pub local_socket_path: Option<String>,
+    pub remote_socket_path: String,
+}
+
+impl StreamLocalForwardPort {
+    fn bind_address(&self) -> &str {
+        local_bind_address(self.bind_address.as_deref())
+    }
+}
+
+/// Server-side listener whose connections are bridged to a local target (like `ssh -R`)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RemoteForwardPort {
+    /// Address the server listens on (default `localhost`)
+    pub bind_address: Option<String>,
+    /// Port the server listens on; 0 lets the server pick one
+    pub remote_port: u16,
+    pub local_host: String,
+    pub local_port: u16,
+}
+
+impl RemoteForwardPort {
+    fn bind_address(&self) -> &str {
+        self.bind_address.as_deref().unwrap_or("localhost")
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SshConfig {
     pub host: String,
@@ -25,15 +128,192 @@ pub struct SshConfig {
     pub username: String,
     pub auth_method: AuthMethod,
     pub forward_ports: Option<Vec<ForwardPort>>,
+    /// Ports the server listens on and forwards back to local targets
+    pub remote_forward_ports: Option<Vec<RemoteForwardPort>>,
+    /// Local SOCKS proxies that reach any destination through the server
+    pub dynamic_forward_ports: Option<Vec<DynamicForwardPort>>,
+    /// Ids the forwards had on the live session, reused when reconnecting so they stay valid
+    #[serde(skip)]
+    pub forward_ids: Vec<(ForwardSpec, u64)>,
+    /// Local ports or sockets bridged to Unix sockets on the server
+    pub stream_local_forward_ports: Option<Vec<StreamLocalForwardPort>>,
+    /// Bastion hosts to tunnel through, in order (like OpenSSH's ProxyJump)
+    pub jump_hosts: Option<Vec<JumpHost>>,
+    /// Local command whose stdin/stdout carries the connection to the first hop.
+    /// `%h`, `%p` and `%r` expand to that hop's host, port and username.
+    pub proxy_command: Option<String>,
+    /// Outbound proxy used to reach the first hop when no ProxyCommand is set
+    pub proxy: Option<ProxyConfig>,
+    /// Seconds without traffic before a keepalive is sent (default 15, 0 disables)
+    pub keepalive_interval: Option<u64>,
+    /// Unanswered keepalives before the connection is considered dead (default 3)
+    pub keepalive_max: Option<usize>,
+    /// Reconnect automatically when the connection drops (default true)
+    pub auto_reconnect: Option<bool>,
+    /// Algorithm preferences for every hop; unset keeps russh's defaults
+    pub algorithms: Option<AlgorithmPreferences>,
+    /// Methods tried in order on the same connection when `auth_method` fails
+    pub fallback_auth_methods: Option<Vec<AuthMethod>>,
+    /// Seconds allowed for reaching each hop's SSH port, and again for its SSH handshake,
+    /// not counting time spent confirming its host key (default 15, 0 disables)
+    pub connect_timeout: Option<u64>,
+    /// Seconds allowed for each authentication attempt, not counting time spent
+    /// answering keyboard-interactive prompts (default 30, 0 disables)
+    pub auth_timeout: Option<u64>,
+    /// Let terminal programs open windows on the local `$DISPLAY` (default false)
+    pub x11_forwarding: Option<bool>,
+    /// Let terminals and commands use the local ssh-agent, e.g. for `git pull` (default false)
+    pub agent_forwarding: Option<bool>,
+    /// Ask the user each time the server wants to use the forwarded agent (default false)
+    pub confirm_agent_use: Option<bool>,
+    /// Variables set on terminals and commands. The server only accepts names its AcceptEnv allows.
+    pub env: Option<HashMap<String, String>>,
+    /// Send the local `LANG` and `LC_*` variables unless `env` sets them (default true)
+    pub forward_locale: Option<bool>,
+}
+
+const DEFAULT_KEEPALIVE_INTERVAL_SECS: u64 = 15;
+const DEFAULT_KEEPALIVE_MAX: usize = 3;
+const DEFAULT_CONNECT_TIMEOUT_SECS: u64 = 15;
+const DEFAULT_AUTH_TIMEOUT_SECS: u64 = 30;
+/// How long an agent channel waits for the user's approval before it is refused
+const AGENT_CONFIRM_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// Seconds from the config, falling back to `default`; 0 means no limit
+fn timeout_secs(secs: Option<u64>, default: u64) -> Option<std::time::Duration> {
+    let secs = secs.unwrap_or(default);
+    (secs > 0).then(|| std::time::Duration::from_secs(secs))
+}
+
+/// Outbound proxy for the TCP connection to the SSH server
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum ProxyConfig {
+    Socks5 {
+        host: String,
+        port: u16,
+        username: Option<String>,
+        password: Option<String>,
+    },
+    Http {
+        host: String,
+        port: u16,
+        username: Option<String>,
+        password: Option<String>,
+    },
+}
+
+/// A single hop in a ProxyJump chain, with its own credentials
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JumpHost {
+    pub host: String,
+    pub port: u16,
+    pub username: String,
+    pub auth_method: AuthMethod,
+    /// Methods tried in order on the same connection when `auth_method` fails
+    pub fallback_auth_methods: Option<Vec<AuthMethod>>,
+}
+
+impl JumpHost {
+    /// Every method to try, in order
+    fn auth_methods(&self) -> Vec<AuthMethod> {
+        std::iter::once(self.auth_method.clone())
+            .chain(self.fallback_auth_methods.iter().flatten().cloned())
+            .collect()
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type")]
 pub enum AuthMethod {
     Password { password: String },
-    PublicKey { key_path: String, passphrase: Option<String> },
+    PublicKey {
+        key_path: String,
+        passphrase: Option<String>,
+        /// OpenSSH certificate; defaults to `<key_path>-cert.pub` when that file exists
+        #[serde(default)]
+        cert_path: Option<String>,
+    },
+    /// PEM/OpenSSH private key text, decoded in memory and never written to disk
+    PrivateKey {
+        private_key: String,
+        passphrase: Option<String>,
+    },
+    Agent,
+    KeyboardInteractive,
+}
+
+impl AuthMethod {
+    /// Short name used in logs and authentication reports
+    pub fn name(&self) -> &'static str {
+        match self {
+            AuthMethod::Password { .. } => "password",
+            AuthMethod::PublicKey { .. } => "publickey",
+            AuthMethod::PrivateKey { .. } => "privatekey",
+            AuthMethod::Agent => "agent",
+            AuthMethod::KeyboardInteractive => "keyboard-interactive",
+        }
+    }
+
+    /// SSH protocol method this maps to
+    fn kind(&self) -> MethodKind {
+        match self {
+            AuthMethod::Password { .. } => MethodKind::Password,
+            AuthMethod::PublicKey { .. } | AuthMethod::PrivateKey { .. } | AuthMethod::Agent => MethodKind::PublicKey,
+            AuthMethod::KeyboardInteractive => MethodKind::KeyboardInteractive,
+        }
+    }
+
+    /// Explanation used when the server turns this method down
+    fn rejection(&self) -> &'static str {
+        match self {
+            AuthMethod::Password { .. } => "The password was rejected by the server.",
+            AuthMethod::PublicKey { .. } | AuthMethod::PrivateKey { .. } => "The key is not authorized on the server.",
+            AuthMethod::Agent => "None of the SSH agent's keys were accepted by the server.",
+            AuthMethod::KeyboardInteractive => "The server rejected the keyboard-interactive responses.",
+        }
+    }
+}
+
+/// Which authentication method succeeded and why the ones tried before it failed
+#[derive(Debug, Clone, Serialize)]
+pub struct AuthReport {
+    /// `None` when every method failed
+    pub method: Option<String>,
+    pub failures: Vec<AuthFailure>,
 }
 
+#[derive(Debug, Clone, Serialize)]
+pub struct AuthFailure {
+    pub method: String,
+    pub reason: String,
+}
+
+/// Error returned when no authentication method succeeded, with why each one failed
+#[derive(Debug)]
+pub struct AuthenticationFailed {
+    pub report: AuthReport,
+}
+
+impl std::fmt::Display for AuthenticationFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.report.failures.as_slice() {
+            [only] => write!(f, "Authentication failed: {}", only.reason),
+            failures => write!(
+                f,
+                "All authentication methods failed: {}",
+                failures
+                    .iter()
+                    .map(|failure| format!("{}: {}", failure.method, failure.reason))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            ),
+        }
+    }
+}
+
+impl std::error::Error for AuthenticationFailed {}
+
 #[derive(Debug, Clone, Serialize)]
 #[allow(dead_code)]
 pub struct SshSession {
@@ -42,25 +322,783 @@ pub struct SshSession {
     pub connected: bool,
 }
 
+/// Connection to the target host. Opening channels only needs shared access;
+/// asking the server to listen for a remote forward needs exclusive access.
+type SessionHandle = Arc<RwLock<client::Handle<Client>>>;
+
 pub struct SshClient {
-    session: Option<Arc<client::Handle<Client>>>,
-    forwarding_tasks: Vec<tokio::task::JoinHandle<()>>,
+    session: Option<SessionHandle>,
+    jump_sessions: Vec<Arc<client::Handle<Client>>>,
+    forwards: Vec<ActiveForward>,
+    next_forward_id: u64,
+    /// Ids from the previous connection, not yet claimed by a re-added forward
+    previous_forward_ids: Vec<(ForwardSpec, u64)>,
+    /// Shared with the target's handler, which routes remote forward connections
+    remote_targets: Arc<tokio::sync::Mutex<HashMap<u32, RemoteTarget>>>,
+    prompts: Option<mpsc::UnboundedSender<UserPrompt>>,
+    /// File every hop's host key is checked against and recorded in
+    known_hosts: Option<std::path::PathBuf>,
+    /// Settings of the last successful connection, kept for reconnecting
+    config: Option<SshConfig>,
+    server: ServerDetails,
+    auth_report: Option<AuthReport>,
+    /// Environment, agent and X11 requests for every terminal and command channel
+    channel_setup: ChannelSetup,
 }
 
 // Re-export the enhanced PTY session as the main PtySession
 pub use pty_session::PtySession;
 
+/// Why a server's host key needs the user's confirmation
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HostKeyPromptKind {
+    /// First time this host (or key type) is seen
+    Unknown,
+    /// The host presented a different key than the one in known_hosts
+    Changed,
+}
+
+/// Host key a server presented, for verifying it out-of-band
+#[derive(Debug, Clone, Serialize)]
+pub struct HostKeyInfo {
+    pub key_type: String,
+    /// SHA256 fingerprint as printed by `ssh-keygen -l`
+    pub fingerprint: String,
+}
+
+impl From<&PublicKey> for HostKeyInfo {
+    fn from(key: &PublicKey) -> Self {
+        Self {
+            key_type: key.algorithm().as_str().to_string(),
+            fingerprint: key.fingerprint(keys::HashAlg::Sha256).to_string(),
+        }
+    }
+}
+
+/// What a server revealed about itself while connecting
+#[derive(Debug, Clone, Default)]
+struct ServerDetails {
+    algorithms: Option<NegotiatedAlgorithms>,
+    host_key: Option<HostKeyInfo>,
+    /// Pre-authentication banner, if the server sent one
+    banner: Option<String>,
+}
+
+/// Host key details shown to the user before trusting a server
+#[derive(Debug, Clone, Serialize)]
+pub struct HostKeyPrompt {
+    pub host: String,
+    pub port: u16,
+    pub key_type: String,
+    pub fingerprint: String,
+    pub kind: HostKeyPromptKind,
+}
+
+/// A single question in a keyboard-interactive challenge
+#[derive(Debug, Clone, Serialize)]
+pub struct KeyboardInteractiveQuestion {
+    pub prompt: String,
+    /// Whether the answer may be shown while typing (false for passwords and OTPs)
+    pub echo: bool,
+}
+
+/// Keyboard-interactive challenge sent by the server (e.g. password, Duo or TOTP prompts)
+#[derive(Debug, Clone, Serialize)]
+pub struct KeyboardInteractivePrompt {
+    pub name: String,
+    pub instructions: String,
+    pub questions: Vec<KeyboardInteractiveQuestion>,
+}
+
+/// A server asking to use the forwarded ssh-agent
+#[derive(Debug, Clone, Serialize)]
+pub struct AgentForwardPrompt {
+    pub host: String,
+    pub port: u16,
+}
+
+/// Questions the SSH layer needs the user to answer.
+/// Each prompt carries the channel its answer must be sent back on.
+pub enum UserPrompt {
+    HostKey {
+        prompt: HostKeyPrompt,
+        reply: oneshot::Sender<bool>,
+    },
+    KeyboardInteractive {
+        prompt: KeyboardInteractivePrompt,
+        reply: oneshot::Sender<Vec<String>>,
+    },
+    /// Raised after connecting, each time a forwarded agent channel is opened
+    AgentForward {
+        prompt: AgentForwardPrompt,
+        reply: oneshot::Sender<bool>,
+    },
+}
+
+pub struct Client {
+    host: String,
+    port: u16,
+    prompts: Option<mpsc::UnboundedSender<UserPrompt>>,
+    /// Filled in during key exchange and authentication
+    server: Arc<std::sync::Mutex<ServerDetails>>,
+    /// Local targets of remote forwards, keyed by the port the server listens on
+    remote_forwards: Arc<tokio::sync::Mutex<HashMap<u32, RemoteTarget>>>,
+    /// Limit for reaching the local target of a server-opened channel
+    local_connect_timeout: std::time::Duration,
+    /// Set on the target's handler when X11 forwarding is enabled
+    x11: Option<Arc<X11Forwarding>>,
+    /// Whether the target may open agent channels, and whether each needs the user's approval
+    agent_forwarding: bool,
+    confirm_agent_use: bool,
+    /// File host keys are checked against and recorded in
+    known_hosts: Option<std::path::PathBuf>,
+    /// True while the user decides whether to trust the host key, which pauses the handshake timeout
+    confirming_host_key: watch::Sender<bool>,
+}
 
-pub struct Client;
+impl Client {
+    fn new(host: &str, port: u16, prompts: Option<mpsc::UnboundedSender<UserPrompt>>) -> Self {
+        Self {
+            host: host.to_string(),
+            port,
+            prompts,
+            server: Arc::new(std::sync::Mutex::new(ServerDetails::default())),
+            remote_forwards: Arc::new(tokio::sync::Mutex::new(HashMap::new())),
+            local_connect_timeout: std::time::Duration::from_secs(DEFAULT_CONNECT_TIMEOUT_SECS),
+            x11: None,
+            agent_forwarding: false,
+            confirm_agent_use: false,
+            known_hosts: known_hosts::default_path(),
+            confirming_host_key: watch::Sender::new(false),
+        }
+    }
+
+    /// Ask the user whether to trust a host key. Without a prompt channel the key is rejected.
+    async fn confirm_host_key(&self, prompt: HostKeyPrompt) -> bool {
+        let Some(prompts) = &self.prompts else {
+            tracing::warn!("No prompt handler available, rejecting untrusted host key for {}", self.host);
+            return false;
+        };
+
+        let (reply, answer) = oneshot::channel();
+        if prompts.send(UserPrompt::HostKey { prompt, reply }).is_err() {
+            return false;
+        }
+        self.confirming_host_key.send_replace(true);
+        let accepted = answer.await.unwrap_or(false);
+        self.confirming_host_key.send_replace(false);
+        accepted
+    }
+}
 
 impl client::Handler for Client {
     type Error = russh::Error;
 
-    fn check_server_key(
+    async fn kex_done(
+        &mut self,
+        _shared_secret: Option<&[u8]>,
+        names: &Names,
+        _session: &mut client::Session,
+    ) -> Result<(), Self::Error> {
+        let negotiated = NegotiatedAlgorithms::from(names);
+        tracing::debug!("Negotiated algorithms with {}:{}: {:?}", self.host, self.port, negotiated);
+        if let Ok(mut server) = self.server.lock() {
+            server.algorithms = Some(negotiated);
+        }
+        Ok(())
+    }
+
+    async fn auth_banner(
+        &mut self,
+        banner: &str,
+        _session: &mut client::Session,
+    ) -> Result<(), Self::Error> {
+        tracing::debug!("Banner from {}:{}: {}", self.host, self.port, banner);
+        if let Ok(mut server) = self.server.lock() {
+            // Servers may send the banner in several messages
+            server.banner.get_or_insert_with(String::new).push_str(banner);
+        }
+        Ok(())
+    }
+
+    async fn server_channel_open_forwarded_tcpip(
+        &mut self,
+        channel: Channel<client::Msg>,
+        connected_address: &str,
+        connected_port: u32,
+        originator_address: &str,
+        originator_port: u32,
+        _session: &mut client::Session,
+    ) -> Result<(), Self::Error> {
+        tracing::debug!(
+            "Remote forward {}:{} connection from {}:{}",
+            connected_address, connected_port, originator_address, originator_port
+        );
+        // Looked up in the task: the map is held while a forward's port is being requested
+        let remote_forwards = self.remote_forwards.clone();
+        let connected_address = connected_address.to_string();
+        let connect_timeout = self.local_connect_timeout;
+        tokio::spawn(async move {
+            let target = remote_forwards.lock().await.get(&connected_port).cloned();
+            let Some(RemoteTarget { forward, stats }) = target else {
+                tracing::warn!(
+                    "Server opened a forwarded channel for {}:{}, which was never requested",
+                    connected_address, connected_port
+                );
+                let _ = channel.close().await;
+                return;
+            };
+
+            let _active = ConnectionGuard::new(&stats);
+            match net::connect(&forward.local_host, forward.local_port, Some(connect_timeout)).await {
+                Ok(stream) => bridge(channel, stream, &stats).await,
+                Err(e) => {
+                    tracing::error!("Remote forward {} could not reach its local target: {}", connected_port, e);
+                    let _ = channel.close().await;
+                }
+            }
+        });
+        Ok(())
+    }
+
+    async fn server_channel_open_x11(
+        &mut self,
+        channel: Channel<client::Msg>,
+        originator_address: &str,
+        originator_port: u32,
+        _session: &mut client::Session,
+    ) -> Result<(), Self::Error> {
+        let Some(x11) = self.x11.clone() else {
+            tracing::warn!("Server opened an X11 channel, but X11 forwarding was not requested");
+            let _ = channel.close().await;
+            return Ok(());
+        };
+
+        tracing::debug!("X11 connection from {}:{}", originator_address, originator_port);
+        let connect_timeout = self.local_connect_timeout;
+        tokio::spawn(async move {
+            if let Err(e) = x11.forward(channel, connect_timeout).await {
+                tracing::warn!("X11 forwarding failed: {}", e);
+            }
+        });
+        Ok(())
+    }
+
+    async fn server_channel_open_agent_forward(
+        &mut self,
+        channel: Channel<client::Msg>,
+        _session: &mut client::Session,
+    ) -> Result<(), Self::Error> {
+        if !self.agent_forwarding {
+            tracing::warn!("Server opened an agent channel, but agent forwarding was not requested");
+            let _ = channel.close().await;
+            return Ok(());
+        }
+
+        // Confirming in a task keeps the connection responsive while the user decides
+        let prompts = self.confirm_agent_use.then(|| self.prompts.clone());
+        let prompt = AgentForwardPrompt {
+            host: self.host.clone(),
+            port: self.port,
+        };
+        tokio::spawn(async move {
+            if let Some(prompts) = prompts {
+                if !confirm_agent_use(prompts, prompt, AGENT_CONFIRM_TIMEOUT).await {
+                    tracing::info!("Use of the forwarded agent was refused");
+                    let _ = channel.close().await;
+                    return;
+                }
+            }
+            match agent::connect_stream().await {
+                // Agent traffic is not reported per forward
+                Ok(stream) => bridge(channel, stream, &ForwardStats::default()).await,
+                Err(e) => {
+                    tracing::warn!("{}", e);
+                    let _ = channel.close().await;
+                }
+            }
+        });
+        Ok(())
+    }
+
+    async fn check_server_key(
         &mut self,
-        _server_public_key: &PublicKey,
-    ) -> impl std::future::Future<Output = Result<bool, Self::Error>> + Send {
-        async { Ok(true) } // In production, verify the server key
+        server_public_key: &PublicKey,
+    ) -> Result<bool, Self::Error> {
+        let host_key = HostKeyInfo::from(server_public_key);
+        if let Ok(mut server) = self.server.lock() {
+            server.host_key = Some(host_key.clone());
+        }
+
+        let path = self.known_hosts.clone();
+        let status = match &path {
+            Some(path) => known_hosts::check(path, &self.host, self.port, server_public_key)
+                .unwrap_or_else(|e| {
+                    tracing::warn!("Failed to check known_hosts: {}", e);
+                    known_hosts::HostKeyStatus::Unknown
+                }),
+            None => known_hosts::HostKeyStatus::Unknown,
+        };
+
+        let (kind, stale_lines) = match status {
+            known_hosts::HostKeyStatus::Known => return Ok(true),
+            known_hosts::HostKeyStatus::Revoked => {
+                tracing::error!("Host key for {}:{} is marked as revoked", self.host, self.port);
+                return Ok(false);
+            }
+            known_hosts::HostKeyStatus::Unknown => (HostKeyPromptKind::Unknown, Vec::new()),
+            known_hosts::HostKeyStatus::Changed { lines } => {
+                tracing::warn!(
+                    "Host key for {}:{} has changed (known_hosts lines {:?})",
+                    self.host, self.port, lines
+                );
+                (HostKeyPromptKind::Changed, lines)
+            }
+        };
+
+        let prompt = HostKeyPrompt {
+            host: self.host.clone(),
+            port: self.port,
+            key_type: host_key.key_type,
+            fingerprint: host_key.fingerprint,
+            kind,
+        };
+
+        if !self.confirm_host_key(prompt).await {
+            return Ok(false);
+        }
+
+        // Record the accepted key, replacing any stale entries for this host
+        if let Some(path) = &path {
+            if !stale_lines.is_empty() {
+                if let Err(e) = known_hosts::remove_lines(path, &self.host, self.port, &stale_lines) {
+                    tracing::warn!("Failed to remove stale known_hosts entries: {}", e);
+                }
+            }
+            if let Err(e) = known_hosts::learn(path, &self.host, self.port, server_public_key) {
+                tracing::warn!("Failed to record host key in known_hosts: {}", e);
+            }
+        }
+
+        Ok(true)
+    }
+}
+
+/// Offer each identity held by the agent until the server accepts one
+async fn authenticate_with_agent(
+    ssh_session: &mut client::Handle<Client>,
+    username: &str,
+    agent: &mut agent::LocalAgent,
+) -> Result<client::AuthResult> {
+    let identities = agent
+        .request_identities()
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to list SSH agent identities: {}", e))?;
+
+    if identities.is_empty() {
+        return Err(anyhow::anyhow!(
+            "The SSH agent has no identities. Add a key with ssh-add and try again."
+        ));
+    }
+
+    // Try each agent identity in turn until the server accepts one
+    let mut result = client::AuthResult::Failure {
+        remaining_methods: MethodSet::empty(),
+        partial_success: false,
+    };
+    for identity in identities {
+        let hash_alg = identity.algorithm().is_rsa().then_some(keys::HashAlg::Sha256);
+        tracing::debug!("Trying agent identity {}", identity.fingerprint(keys::HashAlg::Sha256));
+
+        match ssh_session
+            .authenticate_publickey_with(username, identity, hash_alg, agent)
+            .await
+        {
+            Ok(client::AuthResult::Success) => {
+                result = client::AuthResult::Success;
+                break;
+            }
+            Ok(failure) => result = failure,
+            Err(e) => tracing::warn!("SSH agent failed to sign: {}", e),
+        }
+    }
+    Ok(result)
+}
+
+/// russh client settings for a connection: keepalives and algorithm preferences
+fn client_config(config: &SshConfig) -> Result<client::Config> {
+    let interval = config.keepalive_interval.unwrap_or(DEFAULT_KEEPALIVE_INTERVAL_SECS);
+    let preferred = match &config.algorithms {
+        Some(preferences) => algorithms::preferred(preferences)?,
+        None => Preferred::default(),
+    };
+    Ok(client::Config {
+        keepalive_interval: (interval > 0).then(|| std::time::Duration::from_secs(interval)),
+        keepalive_max: config.keepalive_max.unwrap_or(DEFAULT_KEEPALIVE_MAX),
+        preferred,
+        ..Default::default()
+    })
+}
+
+/// Await `future`, giving up after `limit` when one is set
+async fn within<F: std::future::Future>(
+    limit: Option<std::time::Duration>,
+    future: F,
+) -> std::result::Result<F::Output, tokio::time::error::Elapsed> {
+    match limit {
+        Some(limit) => tokio::time::timeout(limit, future).await,
+        None => Ok(future.await),
+    }
+}
+
+/// Await an SSH handshake, giving up after `limit` when one is set.
+/// Time spent while `confirming` is true (the user deciding on the host key) doesn't count.
+async fn within_handshake<F: std::future::Future>(
+    limit: Option<std::time::Duration>,
+    mut confirming: watch::Receiver<bool>,
+    future: F,
+) -> Option<F::Output> {
+    let Some(mut remaining) = limit else {
+        return Some(future.await);
+    };
+    tokio::pin!(future);
+
+    loop {
+        let started = std::time::Instant::now();
+        let paused = *confirming.borrow_and_update();
+        let changed = tokio::select! {
+            output = &mut future => return Some(output),
+            _ = tokio::time::sleep(remaining), if !paused => return None,
+            changed = confirming.changed() => changed,
+        };
+        if !paused {
+            remaining = remaining.saturating_sub(started.elapsed());
+        }
+        if changed.is_err() {
+            // The handler is gone, so no prompt can pause the clock any more
+            return tokio::time::timeout(remaining, future).await.ok();
+        }
+    }
+}
+
+/// Expand a leading `~/` to the user's home directory
+fn expand_home(path: &str) -> String {
+    if path.starts_with("~/") {
+        if let Ok(home) = std::env::var("HOME") {
+            return path.replacen("~", &home, 1);
+        }
+    }
+    path.to_string()
+}
+
+/// Copy data both ways between a local connection and an SSH channel until either side closes,
+/// counting the bytes moved in each direction
+async fn bridge<S>(mut channel: Channel<client::Msg>, stream: S, stats: &ForwardStats)
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let (mut local_reader, mut local_writer) = tokio::io::split(stream);
+    let mut channel_writer = channel.make_writer();
+
+    let client_to_server = async {
+        let mut buffer = vec![0u8; 32 * 1024];
+        loop {
+            let read = local_reader.read(&mut buffer).await?;
+            if read == 0 {
+                break;
+            }
+            channel_writer.write_all(&buffer[..read]).await?;
+            stats.sent(read);
+        }
+        Ok::<(), std::io::Error>(())
+    };
+
+    let server_to_client = async {
+        while let Some(msg) = channel.wait().await {
+            match msg {
+                ChannelMsg::Data { ref data }
+                | ChannelMsg::ExtendedData { ref data, .. } => {
+                    let written = local_writer.write_all(data).await;
+                    if written.is_err() {
+                        break;
+                    }
+                    stats.received(data.len());
+                }
+                ChannelMsg::Eof | ChannelMsg::Close => break,
+                _ => {}
+            }
+        }
+        let _ = local_writer.flush().await;
+        Ok::<(), anyhow::Error>(())
+    };
+
+    tokio::select! {
+        _ = client_to_server => (),
+        _ = server_to_client => (),
+    };
+}
+
+/// Accept loop of a local forward: each connection gets its own direct-tcpip channel
+fn spawn_local_forward(
+    session: SessionHandle,
+    listener: TcpListener,
+    forward: ForwardPort,
+    stats: Arc<ForwardStats>,
+) -> tokio::task::JoinHandle<()> {
+    let local_port = forward.local_port;
+    tokio::spawn(async move {
+        while let Ok((stream, client_addr)) = listener.accept().await {
+            tracing::debug!("New connection on forwarded port {}: {}", local_port, client_addr);
+            let session = session.clone();
+            let forward = forward.clone();
+            let stats = stats.clone();
+
+            tokio::spawn(async move {
+                let _active = ConnectionGuard::new(&stats);
+                let opened = session.read().await.channel_open_direct_tcpip(
+                    &forward.remote_host,
+                    forward.remote_port as u32,
+                    "127.0.0.1",
+                    local_port as u32,
+                ).await;
+                match opened {
+                    Ok(channel) => {
+                        bridge(channel, stream, &stats).await;
+                        tracing::debug!("Port forward connection closed for port {}", local_port);
+                    }
+                    Err(e) => {
+                        tracing::error!("Failed to open direct-tcpip channel: {}", e);
+                    }
+                }
+            });
+        }
+        tracing::info!("Port forward listener for port {} stopped", local_port);
+    })
+}
+
+/// Accept loop of a dynamic forward: a SOCKS server opening a direct-tcpip channel per CONNECT request
+fn spawn_dynamic_forward(
+    session: SessionHandle,
+    listener: TcpListener,
+    local_port: u16,
+    stats: Arc<ForwardStats>,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        while let Ok((mut stream, client_addr)) = listener.accept().await {
+            let session = session.clone();
+            let stats = stats.clone();
+
+            tokio::spawn(async move {
+                let _active = ConnectionGuard::new(&stats);
+                let request = match socks::read_request(&mut stream).await {
+                    Ok(request) => request,
+                    Err(e) => {
+                        tracing::debug!("Rejected SOCKS request from {}: {}", client_addr, e);
+                        return;
+                    }
+                };
+                tracing::debug!("SOCKS connect from {} to {}:{}", client_addr, request.host, request.port);
+
+                let opened = session.read().await.channel_open_direct_tcpip(
+                    &request.host,
+                    request.port as u32,
+                    &client_addr.ip().to_string(),
+                    client_addr.port() as u32,
+                ).await;
+                match opened {
+                    Ok(channel) => {
+                        if socks::reply(&mut stream, request.version, true).await.is_ok() {
+                            bridge(channel, stream, &stats).await;
+                        }
+                    }
+                    Err(e) => {
+                        tracing::warn!("SOCKS proxy could not reach {}:{}: {}", request.host, request.port, e);
+                        let _ = socks::reply(&mut stream, request.version, false).await;
+                    }
+                }
+            });
+        }
+        tracing::info!("SOCKS proxy on port {} stopped", local_port);
+    })
+}
+
+/// Ask the user whether the server may use the forwarded agent. Without a prompt channel,
+/// or without an answer within `limit`, the use is refused.
+async fn confirm_agent_use(
+    prompts: Option<mpsc::UnboundedSender<UserPrompt>>,
+    prompt: AgentForwardPrompt,
+    limit: std::time::Duration,
+) -> bool {
+    let Some(prompts) = prompts else {
+        tracing::warn!("No prompt handler available, refusing use of the forwarded agent");
+        return false;
+    };
+
+    let (reply, answer) = oneshot::channel();
+    if prompts.send(UserPrompt::AgentForward { prompt, reply }).is_err() {
+        return false;
+    }
+    match tokio::time::timeout(limit, answer).await {
+        Ok(answer) => answer.unwrap_or(false),
+        Err(_) => {
+            tracing::warn!("No answer to the agent forwarding request, refusing it");
+            false
+        }
+    }
+}
+
+/// Listen on the forward's local port or socket; each connection gets its own
+/// direct-streamlocal channel to the remote socket
+async fn spawn_stream_local_forward(
+    session: SessionHandle,
+    forward: &StreamLocalForwardPort,
+    stats: Arc<ForwardStats>,
+) -> std::io::Result<tokio::task::JoinHandle<()>> {
+    let remote_path = forward.remote_socket_path.clone();
+
+    if let Some(local_path) = &forward.local_socket_path {
+        #[cfg(unix)]
+        {
+            let listener = tokio::net::UnixListener::bind(expand_home(local_path))?;
+            let local_path = local_path.clone();
+            return Ok(tokio::spawn(async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    tokio::spawn(forward_to_socket(session.clone(), remote_path.clone(), stream, stats.clone()));
+                }
+                tracing::info!("Socket forward listener on {} stopped", local_path);
+            }));
+        }
+        #[cfg(not(unix))]
+        {
+            let _ = (session, stats, remote_path);
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                format!("Cannot listen on {}: Unix sockets are not supported on this platform", local_path),
+            ));
+        }
+    }
+
+    let local_port = forward.local_port.unwrap_or(0);
+    let listener = TcpListener::bind((forward.bind_address(), local_port)).await?;
+    Ok(tokio::spawn(async move {
+        while let Ok((stream, client_addr)) = listener.accept().await {
+            tracing::debug!("New connection on socket forward port {}: {}", local_port, client_addr);
+            tokio::spawn(forward_to_socket(session.clone(), remote_path.clone(), stream, stats.clone()));
+        }
+        tracing::info!("Socket forward listener for port {} stopped", local_port);
+    }))
+}
+
+/// Bridge one local connection to a Unix socket on the server
+async fn forward_to_socket<S>(
+    session: SessionHandle,
+    socket_path: String,
+    stream: S,
+    stats: Arc<ForwardStats>,
+) where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let _active = ConnectionGuard::new(&stats);
+    let opened = session.read().await.channel_open_direct_streamlocal(socket_path.as_str()).await;
+    match opened {
+        Ok(channel) => {
+            bridge(channel, stream, &stats).await;
+            tracing::debug!("Socket forward connection to {} closed", socket_path);
+        }
+        Err(e) => tracing::error!("Failed to open direct-streamlocal channel to {}: {}", socket_path, e),
+    }
+}
+
+/// Remove the first entry equal to `item` from an optional list
+fn remove_first<T: PartialEq>(list: &mut Option<Vec<T>>, item: &T) {
+    if let Some(list) = list {
+        if let Some(index) = list.iter().position(|entry| entry == item) {
+            list.remove(index);
+        }
+    }
+}
+
+/// Ask the server to listen for a remote forward and route its connections to the forward's
+/// local target. Returns the port the server listens on.
+async fn request_remote_forward(
+    session: &SessionHandle,
+    targets: &tokio::sync::Mutex<HashMap<u32, RemoteTarget>>,
+    forward: &RemoteForwardPort,
+    stats: Arc<ForwardStats>,
+) -> Result<u32> {
+    let bind_address = forward.bind_address();
+    let target = RemoteTarget {
+        forward: forward.clone(),
+        stats,
+    };
+
+    // The server may open channels as soon as it has answered, so the target must be
+    // known by then: registered up front for a fixed port, while a port the server picks
+    // is registered before the map is released
+    let requested_port = forward.remote_port as u32;
+    let mut picking = None;
+    if requested_port == 0 {
+        picking = Some(targets.lock().await);
+    } else {
+        let mut targets = targets.lock().await;
+        if targets.contains_key(&requested_port) {
+            return Err(anyhow::anyhow!("Port {} on the server is already forwarded", requested_port));
+        }
+        targets.insert(requested_port, target.clone());
+    }
+
+    let requested = session.write().await.tcpip_forward(bind_address, requested_port).await;
+    match (requested, picking) {
+        // The server only reports a port when it picked one itself
+        (Ok(port), Some(mut targets)) => {
+            targets.insert(port, target);
+            Ok(port)
+        }
+        (Ok(_), None) => Ok(requested_port),
+        (Err(e), picking) => {
+            if picking.is_none() {
+                targets.lock().await.remove(&requested_port);
+            }
+            Err(anyhow::anyhow!(
+                "Server refused to forward {}:{}: {}",
+                bind_address, forward.remote_port, e
+            ))
+        }
+    }
+}
+
+/// Authenticate with a decoded private key
+async fn authenticate_key(
+    ssh_session: &mut client::Handle<Client>,
+    username: &str,
+    private_key: keys::PrivateKey,
+) -> Result<client::AuthResult> {
+    // Create key with hash algorithm for authentication
+    // Use SHA-256 for RSA keys (more secure and widely supported by modern servers)
+    let key = PrivateKeyWithHashAlg::new(
+        Arc::new(private_key),
+        Some(keys::HashAlg::Sha256)  // Use SHA-256 instead of legacy SHA-1
+    );
+
+    ssh_session
+        .authenticate_publickey(username, key)
+        .await
+        .map_err(|e| anyhow::anyhow!("Public key authentication failed: {}. The key may not be authorized on the server.", e))
+}
+
+/// Turn a key loading failure into an actionable message
+fn key_error(e: keys::Error, source: &str) -> anyhow::Error {
+    let err_str = e.to_string();
+    if err_str.contains("encrypted") || err_str.contains("passphrase") || err_str.contains("decrypt") {
+        anyhow::anyhow!(
+            "Failed to decrypt SSH key. The key may be encrypted. Please provide the correct passphrase."
+        )
+    } else {
+        anyhow::anyhow!(
+            "Failed to load SSH key {}: {}. Ensure it is a valid SSH private key (RSA, Ed25519, or ECDSA).",
+            source, e
+        )
     }
 }
 
@@ -68,35 +1106,306 @@ impl SshClient {
     pub fn new() -> Self {
         Self { 
             session: None,
-            forwarding_tasks: Vec::new(),
+            jump_sessions: Vec::new(),
+            forwards: Vec::new(),
+            next_forward_id: 1,
+            previous_forward_ids: Vec::new(),
+            remote_targets: Arc::new(tokio::sync::Mutex::new(HashMap::new())),
+            prompts: None,
+            known_hosts: known_hosts::default_path(),
+            config: None,
+            server: ServerDetails::default(),
+            auth_report: None,
+            channel_setup: ChannelSetup::default(),
         }
     }
 
+    /// Route questions that need the user's answer (e.g. untrusted host keys) to `prompts`
+    pub fn with_prompts(mut self, prompts: mpsc::UnboundedSender<UserPrompt>) -> Self {
+        self.prompts = Some(prompts);
+        self
+    }
+
     pub async fn connect(&mut self, config: &SshConfig) -> Result<()> {
-        tracing::info!("Connecting to {}:{}", config.host, config.port);
-        let ssh_config = client::Config::default();
-        let mut ssh_session = client::connect(Arc::new(ssh_config), (&config.host[..], config.port), Client).await
-            .map_err(|e| anyhow::anyhow!("Failed to connect to {}:{}: {}", config.host, config.port, e))?;
+        let jump_hosts = config.jump_hosts.clone().unwrap_or_default();
+        let target = JumpHost {
+            host: config.host.clone(),
+            port: config.port,
+            username: config.username.clone(),
+            auth_method: config.auth_method.clone(),
+            fallback_auth_methods: config.fallback_auth_methods.clone(),
+        };
+
+        // Connect hop by hop: each jump host tunnels the next one through a direct-tcpip channel
+        let ssh_config = Arc::new(client_config(config)?);
+        let connect_timeout = timeout_secs(config.connect_timeout, DEFAULT_CONNECT_TIMEOUT_SECS);
+        let auth_timeout = timeout_secs(config.auth_timeout, DEFAULT_AUTH_TIMEOUT_SECS);
+        let mut jump_sessions: Vec<Arc<client::Handle<Client>>> = Vec::new();
+        let mut server = None;
+        let mut auth_report = None;
+        let mut target_session = None;
+        let x11 = match config.x11_forwarding {
+            Some(true) => match X11Forwarding::from_env().await {
+                Ok(x11) => Some(Arc::new(x11)),
+                Err(e) => {
+                    tracing::warn!("{}", e);
+                    None
+                }
+            },
+            _ => None,
+        };
+        for hop in jump_hosts.iter().chain(std::iter::once(&target)) {
+            let mut handler = Client::new(&hop.host, hop.port, self.prompts.clone());
+            handler.known_hosts = self.known_hosts.clone();
+            // Local targets are bounded even when the connection itself has no limit
+            if let Some(limit) = connect_timeout {
+                handler.local_connect_timeout = limit;
+            }
+            // Only the target opens X11 and agent channels; jump hosts never run the terminal
+            if jump_sessions.len() == jump_hosts.len() {
+                handler.x11 = x11.clone();
+                handler.agent_forwarding = config.agent_forwarding.unwrap_or(false);
+                handler.confirm_agent_use = config.confirm_agent_use.unwrap_or(false);
+            }
+            let ssh_config = ssh_config.clone();
+            let confirming_host_key = handler.confirming_host_key.subscribe();
+            server = Some(handler.server.clone());
+            let remote_targets = handler.remote_forwards.clone();
+
+            let connected = match jump_sessions.last() {
+                None => match &config.proxy_command {
+                    Some(proxy_command) => {
+                        tracing::info!("Connecting to {}:{} via ProxyCommand", hop.host, hop.port);
+                        let stream = proxy::spawn_command(proxy_command, &hop.host, hop.port, &hop.username)?;
+                        let handshake = client::connect_stream(ssh_config, stream, handler);
+                        within_handshake(connect_timeout, confirming_host_key, handshake).await
+                    }
+                    None => match &config.proxy {
+                        Some(proxy) => {
+                            tracing::info!("Connecting to {}:{} via proxy", hop.host, hop.port);
+                            let stream = proxy::connect_via(proxy, &hop.host, hop.port, connect_timeout).await?;
+                            let handshake = client::connect_stream(ssh_config, stream, handler);
+                            within_handshake(connect_timeout, confirming_host_key, handshake).await
+                        }
+                        None => {
+                            tracing::info!("Connecting to {}:{}", hop.host, hop.port);
+                            let stream = net::connect(&hop.host, hop.port, connect_timeout).await?;
+                            let handshake = client::connect_stream(ssh_config, stream, handler);
+                            within_handshake(connect_timeout, confirming_host_key, handshake).await
+                        }
+                    },
+                },
+                Some(jump) => {
+                    tracing::info!("Connecting to {}:{} through jump host", hop.host, hop.port);
+                    let channel = within(
+                        connect_timeout,
+                        jump.channel_open_direct_tcpip(&hop.host[..], hop.port as u32, "127.0.0.1", 0),
+                    )
+                        .await
+                        .map_err(|_| anyhow::anyhow!("Timed out opening a tunnel to {}:{} through the jump host", hop.host, hop.port))?
+                        .map_err(|e| anyhow::anyhow!(
+                            "Jump host could not open a tunnel to {}:{}: {}",
+                            hop.host, hop.port, e
+                        ))?;
+                    let handshake = client::connect_stream(ssh_config, channel.into_stream(), handler);
+                    within_handshake(connect_timeout, confirming_host_key, handshake).await
+                }
+            };
+
+            let connected = connected.ok_or_else(|| {
+                anyhow::anyhow!("Timed out during the SSH handshake with {}:{}", hop.host, hop.port)
+            })?;
+            let mut ssh_session = connected.map_err(|e| match e {
+                russh::Error::UnknownKey => anyhow::anyhow!(
+                    "Host key verification failed for {}:{}. The server's host key was not trusted.",
+                    hop.host, hop.port
+                ),
+                e => anyhow::anyhow!("Failed to connect to {}:{}: {}", hop.host, hop.port, e),
+            })?;
+
+            if jump_sessions.len() < jump_hosts.len() {
+                self.authenticate(&mut ssh_session, &hop.username, &hop.auth_methods(), auth_timeout)
+                    .await
+                    .map_err(|e| anyhow::anyhow!("Jump host {}:{}: {}", hop.host, hop.port, e))?;
+                jump_sessions.push(Arc::new(ssh_session));
+            } else {
+                auth_report = Some(self.authenticate(&mut ssh_session, &hop.username, &hop.auth_methods(), auth_timeout).await?);
+                self.remote_targets = remote_targets;
+                target_session = Some(ssh_session);
+            }
+        }
 
-        tracing::info!("Authenticating user: {}", config.username);
-        let authenticated = match &config.auth_method {
+        // The jump hosts must stay alive to carry the target's traffic
+        self.session = target_session.map(|session| Arc::new(RwLock::new(session)));
+        self.jump_sessions = jump_sessions;
+        self.channel_setup = ChannelSetup {
+            env: channel_setup::environment(config.env.as_ref(), config.forward_locale.unwrap_or(true)),
+            agent_forwarding: config.agent_forwarding.unwrap_or(false),
+            x11,
+        };
+
+        // Start port forwarding if configured, under the ids the forwards had before reconnecting
+        self.previous_forward_ids = config.forward_ids.clone();
+        if let Some(max_id) = self.previous_forward_ids.iter().map(|(_, id)| *id).max() {
+            self.next_forward_id = self.next_forward_id.max(max_id + 1);
+        }
+        if let Some(forward_ports) = &config.forward_ports {
+            if !forward_ports.is_empty() {
+                tracing::info!("Setting up {} port forward(s)", forward_ports.len());
+                self.start_port_forwarding(forward_ports.clone()).await?;
+            }
+        }
+        if let Some(remote_forward_ports) = &config.remote_forward_ports {
+            if !remote_forward_ports.is_empty() {
+                tracing::info!("Setting up {} remote forward(s)", remote_forward_ports.len());
+                for forward in remote_forward_ports.clone() {
+                    self.add_forward(ForwardSpec::Remote(forward)).await?;
+                }
+            }
+        }
+        if let Some(dynamic_forward_ports) = &config.dynamic_forward_ports {
+            if !dynamic_forward_ports.is_empty() {
+                tracing::info!("Setting up {} dynamic forward(s)", dynamic_forward_ports.len());
+                self.start_dynamic_forwarding(dynamic_forward_ports.clone()).await?;
+            }
+        }
+        if let Some(stream_local_forward_ports) = &config.stream_local_forward_ports {
+            if !stream_local_forward_ports.is_empty() {
+                tracing::info!("Setting up {} socket forward(s)", stream_local_forward_ports.len());
+                for forward in stream_local_forward_ports.clone() {
+                    self.add_forward(ForwardSpec::StreamLocal(forward)).await?;
+                }
+            }
+        }
+
+        self.previous_forward_ids.clear();
+        let mut config = config.clone();
+        config.forward_ids = self.forwards.iter().map(|forward| (forward.spec.clone(), forward.id)).collect();
+        self.config = Some(config);
+        self.server = server.and_then(|s| s.lock().ok().map(|s| s.clone())).unwrap_or_default();
+        self.auth_report = auth_report;
+        tracing::info!("SSH connection established");
+        Ok(())
+    }
+
+    /// Settings used for the current connection
+    pub fn config(&self) -> Option<&SshConfig> {
+        self.config.as_ref()
+    }
+
+    /// Algorithms negotiated with the target host
+    pub fn negotiated_algorithms(&self) -> Option<&NegotiatedAlgorithms> {
+        self.server.algorithms.as_ref()
+    }
+
+    /// Host key the target host presented
+    pub fn host_key(&self) -> Option<&HostKeyInfo> {
+        self.server.host_key.as_ref()
+    }
+
+    /// Banner the target host sent before authentication
+    pub fn banner(&self) -> Option<&str> {
+        self.server.banner.as_deref()
+    }
+
+    /// How the user was authenticated on the target host
+    pub fn auth_report(&self) -> Option<&AuthReport> {
+        self.auth_report.as_ref()
+    }
+
+    /// Try each method in order on the same transport until one succeeds, as OpenSSH does.
+    /// Methods the server has said it won't accept are skipped.
+    async fn authenticate(
+        &self,
+        ssh_session: &mut client::Handle<Client>,
+        username: &str,
+        methods: &[AuthMethod],
+        auth_timeout: Option<std::time::Duration>,
+    ) -> Result<AuthReport> {
+        tracing::info!("Authenticating user: {}", username);
+        let mut failures = Vec::new();
+        let mut allowed: Option<MethodSet> = None;
+
+        for method in methods {
+            if let Some(allowed) = allowed.as_ref().filter(|allowed| !allowed.contains(&method.kind())) {
+                let allowed: Vec<&str> = allowed.iter().map(<&str>::from).collect();
+                failures.push(AuthFailure {
+                    method: method.name().to_string(),
+                    reason: format!("Not accepted by the server (allowed: {}).", allowed.join(", ")),
+                });
+                continue;
+            }
+
+            tracing::debug!("Trying {} authentication", method.name());
+            let attempt = self.authenticate_with(ssh_session, username, method);
+            let result = match method {
+                // Time spent waiting on the user's answers doesn't count against the limit
+                AuthMethod::KeyboardInteractive => attempt.await,
+                _ => match within(auth_timeout, attempt).await {
+                    Ok(result) => result,
+                    Err(_) => {
+                        // The server never answered, so the transport can't be trusted with another attempt
+                        let reason = format!("Timed out after {}s.", auth_timeout.unwrap_or_default().as_secs());
+                        tracing::warn!("{} authentication failed: {}", method.name(), reason);
+                        failures.push(AuthFailure {
+                            method: method.name().to_string(),
+                            reason,
+                        });
+                        break;
+                    }
+                },
+            };
+            let reason = match result {
+                Ok(client::AuthResult::Success) => {
+                    tracing::info!("Authentication successful for {} using {}", username, method.name());
+                    return Ok(AuthReport {
+                        method: Some(method.name().to_string()),
+                        failures,
+                    });
+                }
+                Ok(client::AuthResult::Failure { remaining_methods, partial_success }) => {
+                    allowed = Some(remaining_methods);
+                    if partial_success {
+                        "Accepted, but the server requires another method as well.".to_string()
+                    } else {
+                        method.rejection().to_string()
+                    }
+                }
+                Err(e) => e.to_string(),
+            };
+            tracing::warn!("{} authentication failed: {}", method.name(), reason);
+            failures.push(AuthFailure {
+                method: method.name().to_string(),
+                reason,
+            });
+
+            if ssh_session.is_closed() {
+                break;
+            }
+        }
+
+        Err(AuthenticationFailed {
+            report: AuthReport { method: None, failures },
+        }
+        .into())
+    }
+
+    /// Attempt a single authentication method
+    async fn authenticate_with(
+        &self,
+        ssh_session: &mut client::Handle<Client>,
+        username: &str,
+        auth_method: &AuthMethod,
+    ) -> Result<client::AuthResult> {
+        let authenticated = match auth_method {
             AuthMethod::Password { password } => {
                 ssh_session
-                    .authenticate_password(&config.username, password)
+                    .authenticate_password(username, password)
                     .await
                     .map_err(|e| anyhow::anyhow!("Password authentication failed: {}", e))?
             }
-            AuthMethod::PublicKey { key_path, passphrase } => {
-                // Expand tilde in path
-                let expanded_path = if key_path.starts_with("~/") {
-                    if let Some(home) = std::env::var("HOME").ok() {
-                        key_path.replacen("~", &home, 1)
-                    } else {
-                        key_path.clone()
-                    }
-                } else {
-                    key_path.clone()
-                };
+            AuthMethod::PublicKey { key_path, passphrase, cert_path } => {
+                let expanded_path = expand_home(key_path);
 
                 // Check if file exists
                 if !std::path::Path::new(&expanded_path).exists() {
@@ -108,204 +1417,357 @@ impl SshClient {
 
                 // Load the key using russh's built-in function (supports RSA, Ed25519, ECDSA)
                 let private_key = keys::load_secret_key(&expanded_path, passphrase.as_deref())
-                    .map_err(|e| {
-                        let err_str = e.to_string();
-                        if err_str.contains("encrypted") || err_str.contains("passphrase") || err_str.contains("decrypt") {
-                            anyhow::anyhow!(
-                                "Failed to decrypt SSH key. The key may be encrypted. Please provide the correct passphrase."
-                            )
-                        } else {
-                            anyhow::anyhow!(
-                                "Failed to load SSH key from {}: {}. Ensure the file is a valid SSH private key (RSA, Ed25519, or ECDSA).",
-                                key_path, e
-                            )
-                        }
-                    })?;
+                    .map_err(|e| key_error(e, &format!("from {}", key_path)))?;
 
-                // Create key with hash algorithm for authentication
-                // Use SHA-256 for RSA keys (more secure and widely supported by modern servers)
-                let key = PrivateKeyWithHashAlg::new(
-                    Arc::new(private_key), 
-                    Some(keys::HashAlg::Sha256)  // Use SHA-256 instead of legacy SHA-1
+                // Prefer certificate authentication when a certificate is configured or sits next to the key
+                let expanded_cert_path = cert_path.as_deref().map(expand_home);
+                let cert_file = certificate::find(
+                    std::path::Path::new(&expanded_path),
+                    expanded_cert_path.as_deref().map(std::path::Path::new),
                 );
 
-                ssh_session
-                    .authenticate_publickey(&config.username, key)
-                    .await
-                    .map_err(|e| anyhow::anyhow!("Public key authentication failed: {}. The key may not be authorized on the server.", e))?
+                if let Some(cert_file) = cert_file {
+                    let cert = certificate::load(&cert_file, &private_key)?;
+                    tracing::info!("Authenticating with SSH certificate {} (key id: {})", cert_file.display(), cert.key_id());
+
+                    ssh_session
+                        .authenticate_openssh_cert(username, Arc::new(private_key), cert)
+                        .await
+                        .map_err(|e| anyhow::anyhow!("Certificate authentication failed: {}. The certificate authority may not be trusted by the server.", e))?
+                } else {
+                    authenticate_key(ssh_session, username, private_key).await?
+                }
+            }
+            AuthMethod::PrivateKey { private_key, passphrase } => {
+                // Decoded entirely in memory so profiles can carry their own keys
+                let private_key = keys::decode_secret_key(private_key, passphrase.as_deref())
+                    .map_err(|e| key_error(e, "from the connection profile"))?;
+
+                authenticate_key(ssh_session, username, private_key).await?
+            }
+            AuthMethod::Agent => {
+                let mut agent = agent::connect().await?;
+                authenticate_with_agent(ssh_session, username, &mut agent).await?
+            }
+            AuthMethod::KeyboardInteractive => {
+                self.authenticate_keyboard_interactive(ssh_session, username).await?
             }
         };
 
-        // Check if authentication was successful
-        match &authenticated {
-            client::AuthResult::Success => {
-                tracing::info!("Authentication successful for {}", config.username);
-            },
-            client::AuthResult::Failure { .. } => {
-                return Err(anyhow::anyhow!("Authentication failed. Please check your credentials and try again."));
-            },
-        }
+        Ok(authenticated)
+    }
 
-        self.session = Some(Arc::new(ssh_session));
+    /// Answer keyboard-interactive challenges through the prompt channel until the server decides
+    async fn authenticate_keyboard_interactive(
+        &self,
+        ssh_session: &mut client::Handle<Client>,
+        username: &str,
+    ) -> Result<client::AuthResult> {
+        let mut response = ssh_session
+            .authenticate_keyboard_interactive_start(username, None)
+            .await
+            .map_err(|e| anyhow::anyhow!("Keyboard-interactive authentication failed: {}", e))?;
 
-        // Start port forwarding if configured
-        if let Some(forward_ports) = &config.forward_ports {
-            if !forward_ports.is_empty() {
-                tracing::info!("Setting up {} port forward(s)", forward_ports.len());
-                self.start_port_forwarding(forward_ports.clone()).await?;
+        // The server may send any number of challenge rounds before deciding
+        let result = loop {
+            match response {
+                client::KeyboardInteractiveAuthResponse::Success => {
+                    break client::AuthResult::Success;
+                }
+                client::KeyboardInteractiveAuthResponse::Failure { remaining_methods, partial_success } => {
+                    break client::AuthResult::Failure { remaining_methods, partial_success };
+                }
+                client::KeyboardInteractiveAuthResponse::InfoRequest { name, instructions, prompts } => {
+                    let answers = if prompts.is_empty() {
+                        Vec::new()
+                    } else {
+                        let prompt = KeyboardInteractivePrompt {
+                            name,
+                            instructions,
+                            questions: prompts
+                                .into_iter()
+                                .map(|p| KeyboardInteractiveQuestion { prompt: p.prompt, echo: p.echo })
+                                .collect(),
+                        };
+                        self.ask_keyboard_interactive(prompt).await?
+                    };
+
+                    response = ssh_session
+                        .authenticate_keyboard_interactive_respond(answers)
+                        .await
+                        .map_err(|e| anyhow::anyhow!("Keyboard-interactive authentication failed: {}", e))?;
+                }
             }
+        };
+        Ok(result)
+    }
+
+    /// Send a keyboard-interactive challenge to the user and wait for the answers
+    async fn ask_keyboard_interactive(&self, prompt: KeyboardInteractivePrompt) -> Result<Vec<String>> {
+        let prompts = self.prompts.as_ref().ok_or_else(|| {
+            anyhow::anyhow!("Keyboard-interactive authentication requires user input, but no prompt handler is available")
+        })?;
+
+        let expected = prompt.questions.len();
+        let (reply, answer) = oneshot::channel();
+        prompts
+            .send(UserPrompt::KeyboardInteractive { prompt, reply })
+            .map_err(|_| anyhow::anyhow!("Keyboard-interactive prompt handler is unavailable"))?;
+
+        let answers = answer
+            .await
+            .map_err(|_| anyhow::anyhow!("Keyboard-interactive authentication was cancelled"))?;
+        if answers.len() != expected {
+            return Err(anyhow::anyhow!(
+                "Expected {} keyboard-interactive response(s), got {}",
+                expected,
+                answers.len()
+            ));
         }
+        Ok(answers)
+    }
 
-        tracing::info!("SSH connection established");
+    /// Start local forwards. A port that can't be bound doesn't stop the others;
+    /// it is reported with a failed status by `forwards()`.
+    pub async fn start_port_forwarding(&mut self, forward_ports: Vec<ForwardPort>) -> Result<()> {
+        for forward in forward_ports {
+            self.add_forward(ForwardSpec::Local(forward)).await?;
+        }
         Ok(())
     }
 
-    pub async fn start_port_forwarding(&mut self, forward_ports: Vec<ForwardPort>) -> Result<()> {
+    /// Run a SOCKS server on each local port, opening a direct-tcpip channel per CONNECT request
+    pub async fn start_dynamic_forwarding(&mut self, forwards: Vec<DynamicForwardPort>) -> Result<()> {
+        for forward in forwards {
+            self.add_forward(ForwardSpec::Dynamic(forward)).await?;
+        }
+        Ok(())
+    }
+
+    /// Start a forward on the live session. A forward that can't listen, locally or on the
+    /// server, is still tracked, with a failed status, so it shows up in `forwards()`.
+    pub async fn add_forward(&mut self, spec: ForwardSpec) -> Result<ForwardInfo> {
         let session = self.session.as_ref().ok_or_else(|| anyhow::anyhow!("Not connected"))?.clone();
-        
-        for forward in forward_ports {
-            let session_clone = session.clone();
-            let local_port = forward.local_port;
-            let remote_host = forward.remote_host.clone();
-            let remote_port = forward.remote_port;
-            
-            let addr = format!("127.0.0.1:{}", local_port);
-            
-            // CRITICAL: Bind asynchronously and handle errors
-            let listener = match TcpListener::bind(&addr).await {
-                Ok(l) => l,
-                Err(e) => {
-                    tracing::error!("Failed to bind to local port {}: {}", local_port, e);
-                    return Err(anyhow::anyhow!("Failed to bind to local port {}: {}", local_port, e));
+        let stats: Arc<ForwardStats> = Arc::default();
+
+        let (listen_on, description, started) = match &spec {
+            ForwardSpec::Local(forward) => {
+                let listen_on = format!("{} port {}", forward.bind_address(), forward.local_port);
+                let started = TcpListener::bind((forward.bind_address(), forward.local_port))
+                    .await
+                    .map(|listener| (Some(spawn_local_forward(session, listener, forward.clone(), stats.clone())), None))
+                    .map_err(|e| format!("Failed to bind to {}: {}", listen_on, e));
+                (listen_on, format!("to {}:{}", forward.remote_host, forward.remote_port), started)
+            }
+            ForwardSpec::Dynamic(forward) => {
+                let listen_on = format!("{} port {}", forward.bind_address(), forward.local_port);
+                let started = TcpListener::bind((forward.bind_address(), forward.local_port))
+                    .await
+                    .map(|listener| (Some(spawn_dynamic_forward(session, listener, forward.local_port, stats.clone())), None))
+                    .map_err(|e| format!("Failed to bind to {}: {}", listen_on, e));
+                (listen_on, "as a SOCKS proxy".to_string(), started)
+            }
+            ForwardSpec::StreamLocal(forward) => {
+                let listen_on = match (&forward.local_socket_path, forward.local_port) {
+                    (Some(path), _) => Some(path.clone()),
+                    (None, Some(port)) => Some(format!("{} port {}", forward.bind_address(), port)),
+                    (None, None) => None,
+                };
+                let started = match &listen_on {
+                    Some(listen_on) => spawn_stream_local_forward(session, forward, stats.clone())
+                        .await
+                        .map(|task| (Some(task), None))
+                        .map_err(|e| format!("Failed to bind to {}: {}", listen_on, e)),
+                    None => Err("A socket forward needs a local port or a local socket path".to_string()),
+                };
+                (
+                    listen_on.unwrap_or_default(),
+                    format!("to {} on the server", forward.remote_socket_path),
+                    started,
+                )
+            }
+            ForwardSpec::Remote(forward) => {
+                let requested = request_remote_forward(&session, &self.remote_targets, forward, stats.clone()).await;
+                let port = requested.as_ref().map_or(forward.remote_port as u32, |&port| port);
+                (
+                    format!("{} port {} on the server", forward.bind_address(), port),
+                    format!("to {}:{}", forward.local_host, forward.local_port),
+                    requested.map(|port| (None, Some(port))).map_err(|e| e.to_string()),
+                )
+            }
+        };
+
+        let (status, task, bound_port) = match started {
+            Ok((task, bound_port)) => {
+                tracing::info!("Forwarding {} {}", listen_on, description);
+                (ForwardStatus::Listening, task, bound_port)
+            }
+            Err(error) => {
+                tracing::error!("{}", error);
+                (ForwardStatus::Failed { error }, None, None)
+            }
+        };
+
+        // Keep the forward and its id across reconnects
+        let id = self.forward_id(&spec);
+        if let (ForwardStatus::Listening, Some(config)) = (&status, self.config.as_mut()) {
+            config.forward_ids.push((spec.clone(), id));
+            match &spec {
+                ForwardSpec::Local(forward) => config.forward_ports.get_or_insert_with(Vec::new).push(forward.clone()),
+                ForwardSpec::Remote(forward) => {
+                    config.remote_forward_ports.get_or_insert_with(Vec::new).push(forward.clone())
                 }
-            };
-                
-            tracing::info!("Forwarding local port {} to {}:{}", local_port, remote_host, remote_port);
-            
-            let handle = tokio::spawn(async move {
-                while let Ok((stream, client_addr)) = listener.accept().await {
-                    tracing::debug!("New connection on forwarded port {}: {}", local_port, client_addr);
-                    let session_clone = session_clone.clone();
-                    let remote_host = remote_host.clone();
-                    let remote_port = remote_port;
-                    let local_port = local_port;
-                    
-                    tokio::spawn(async move {
-                        match session_clone.channel_open_direct_tcpip(
-                            &remote_host,
-                            remote_port as u32,
-                            "127.0.0.1",
-                            local_port as u32,
-                        ).await {
-                            Ok(mut channel) => {
-                                let (mut tcp_reader, mut tcp_writer) = tokio::io::split(stream);
-                                let mut channel_writer = channel.make_writer();
-                                
-                                // Bidirectional copy using tokio::io::copy and manual loop
-                                let client_to_server = tokio::io::copy(&mut tcp_reader, &mut channel_writer);
-                                
-                                let server_to_client = async {
-                                    while let Some(msg) = channel.wait().await {
-                                        match msg {
-                                            ChannelMsg::Data { ref data } => {
-                                                if tcp_writer.write_all(data).await.is_err() {
-                                                    break;
-                                                }
-                                            }
-                                            ChannelMsg::ExtendedData { ref data, .. } => {
-                                                if tcp_writer.write_all(data).await.is_err() {
-                                                    break;
-                                                }
-                                            }
-                                            ChannelMsg::Eof | ChannelMsg::Close => break,
-                                            _ => {}
-                                        }
-                                    }
-                                    let _ = tcp_writer.flush().await;
-                                    Ok::<(), anyhow::Error>(())
-                                };
-
-                                let _ = tokio::select! {
-                                    _ = client_to_server => (),
-                                    _ = server_to_client => (),
-                                };
-                                tracing::debug!("Port forward connection closed for port {}", local_port);
-                            }
-                            Err(e) => {
-                                tracing::error!("Failed to open direct-tcpip channel: {}", e);
-                            }
-                        }
-                    });
+                ForwardSpec::Dynamic(forward) => {
+                    config.dynamic_forward_ports.get_or_insert_with(Vec::new).push(forward.clone())
                 }
-                tracing::info!("Port forward listener for port {} stopped", local_port);
-            });
-            
-            self.forwarding_tasks.push(handle);
+                ForwardSpec::StreamLocal(forward) => {
+                    config.stream_local_forward_ports.get_or_insert_with(Vec::new).push(forward.clone())
+                }
+            }
         }
-        
-        Ok(())
+
+        Ok(self.track_forward(id, spec, status, stats, task, bound_port))
     }
 
-    // Changed to &self instead of &mut self to allow concurrent access
-    pub async fn execute_command(&self, command: &str) -> Result<String> {
-        if let Some(session) = &self.session {
-            let mut channel = session.channel_open_session().await?;
-            channel.exec(true, command).await?;
+    /// Stop a forward and drop it from the connection settings, even if the server fails
+    /// to stop listening for a remote forward. Connections already made through it stay
+    /// open until either end closes them.
+    pub async fn remove_forward(&mut self, id: u64) -> Result<()> {
+        let index = self
+            .forwards
+            .iter()
+            .position(|forward| forward.id == id)
+            .ok_or_else(|| anyhow::anyhow!("No forward with id {}", id))?;
 
-            let mut output = String::new();
-            let mut code = None;
-            let mut eof_received = false;
+        let cancelled = match (&self.forwards[index].spec, self.forwards[index].bound_port, &self.session) {
+            (ForwardSpec::Remote(remote), Some(port), Some(session)) => session
+                .read()
+                .await
+                .cancel_tcpip_forward(remote.bind_address(), port)
+                .await
+                .map_err(|e| anyhow::anyhow!("Server failed to stop forwarding port {}: {}", port, e)),
+            _ => Ok(()),
+        };
 
-            loop {
-                let msg = channel.wait().await;
-                match msg {
-                    Some(ChannelMsg::Data { ref data }) => {
-                        output.push_str(&String::from_utf8_lossy(data));
-                    }
-                    Some(ChannelMsg::ExitStatus { exit_status }) => {
-                        code = Some(exit_status);
-                        if eof_received {
-                            break;
-                        }
-                    }
-                    Some(ChannelMsg::Eof) => {
-                        eof_received = true;
-                        if code.is_some() {
-                            break;
-                        }
-                    }
-                    Some(ChannelMsg::Close) => {
-                        break;
-                    }
-                    None => {
-                        break;
-                    }
-                    _ => {}
+        let forward = self.forwards.remove(index);
+        forward.stop();
+        if let (ForwardSpec::Remote(_), Some(port)) = (&forward.spec, forward.bound_port) {
+            self.remote_targets.lock().await.remove(&port);
+        }
+
+        if let Some(config) = self.config.as_mut() {
+            config.forward_ids.retain(|(_, forward_id)| *forward_id != id);
+            match &forward.spec {
+                ForwardSpec::Local(local) => remove_first(&mut config.forward_ports, local),
+                ForwardSpec::Remote(remote) => remove_first(&mut config.remote_forward_ports, remote),
+                ForwardSpec::Dynamic(dynamic) => remove_first(&mut config.dynamic_forward_ports, dynamic),
+                ForwardSpec::StreamLocal(stream_local) => {
+                    remove_first(&mut config.stream_local_forward_ports, stream_local)
                 }
             }
+        }
+        tracing::info!("Removed forward {}", id);
+        cancelled
+    }
 
-            // Consider success if we got output and no explicit error code, or code 0
-            match code {
-                Some(0) => Ok(output),
-                None if !output.is_empty() => Ok(output), // No exit code but got output = success
-                _ => Err(anyhow::anyhow!("Command failed with code: {:?}", code))
-            }
-        } else {
-            Err(anyhow::anyhow!("Not connected"))
+    /// Every forward of this connection with its status and active connection count
+    pub fn forwards(&self) -> Vec<ForwardInfo> {
+        self.forwards.iter().map(ActiveForward::info).collect()
+    }
+
+    /// Id for a new forward: the one the same forward had before reconnecting, or a fresh one
+    fn forward_id(&mut self, spec: &ForwardSpec) -> u64 {
+        if let Some(index) = self.previous_forward_ids.iter().position(|(previous, _)| previous == spec) {
+            return self.previous_forward_ids.remove(index).1;
+        }
+        let id = self.next_forward_id;
+        self.next_forward_id += 1;
+        id
+    }
+
+    fn track_forward(
+        &mut self,
+        id: u64,
+        spec: ForwardSpec,
+        status: ForwardStatus,
+        stats: Arc<ForwardStats>,
+        task: Option<tokio::task::JoinHandle<()>>,
+        bound_port: Option<u32>,
+    ) -> ForwardInfo {
+        let forward = ActiveForward {
+            id,
+            spec,
+            status,
+            stats,
+            task,
+            bound_port,
+        };
+
+        let info = forward.info();
+        if let Some(warning) = &info.warning {
+            tracing::warn!("Forward {}: {}", info.id, warning);
+        }
+        self.forwards.push(forward);
+        info
+    }
+
+    /// Start a command on its own channel; its output is read with `RunningCommand::wait`
+    pub async fn start_command(&self, command: &str) -> Result<RunningCommand> {
+        let session = self.session.as_ref().ok_or_else(|| anyhow::anyhow!("Not connected"))?;
+        let started = std::time::Instant::now();
+
+        let mut channel = session.read().await.channel_open_session().await?;
+        let rejected_env = self.channel_setup.apply(&mut channel, false).await?;
+        channel.exec(true, command).await?;
+        let running = RunningCommand::new(channel, started, rejected_env);
+        // Programs that read stdin get end of file instead of waiting forever
+        running.control().close_input().await?;
+        Ok(running)
+    }
+
+    /// Run a command and collect its stdout, stderr and exit status.
+    /// A command that fails still returns `Ok`; check `status`.
+    pub async fn exec(&self, command: &str) -> Result<ExecResult> {
+        let mut running = self.start_command(command).await?;
+
+        let (mut stdout, mut stderr) = (Vec::new(), Vec::new());
+        let status = running
+            .wait(|stream, data| match stream {
+                OutputStream::Stdout => stdout.extend_from_slice(data),
+                OutputStream::Stderr => stderr.extend_from_slice(data),
+            })
+            .await;
+
+        Ok(ExecResult {
+            stdout,
+            stderr,
+            status,
+            duration_ms: running.duration_ms(),
+            rejected_env: running.rejected_env,
+        })
+    }
+
+    /// Run a command and return its stdout, failing with its stderr unless it exits with 0.
+    /// Environment variables the server refused are not reported here; use `exec` to see them.
+    pub async fn execute_command(&self, command: &str) -> Result<String> {
+        let result = self.exec(command).await?;
+        match result.error_message() {
+            None => Ok(String::from_utf8_lossy(&result.stdout).into_owned()),
+            Some(error) => Err(anyhow::anyhow!(error)),
         }
     }
 
     pub async fn disconnect(&mut self) -> Result<()> {
         // Stop all port forwarding tasks
-        for handle in self.forwarding_tasks.drain(..) {
-            handle.abort();
+        for forward in self.forwards.drain(..) {
+            forward.stop();
         }
 
         if let Some(session) = self.session.take() {
             // Try to unwrap Arc, if we're the only owner
             match Arc::try_unwrap(session) {
                 Ok(session) => {
-                    session.disconnect(Disconnect::ByApplication, "", "English").await?;
+                    session.into_inner().disconnect(Disconnect::ByApplication, "", "English").await?;
                 }
                 Err(arc_session) => {
                     // Other references exist, just drop our reference
@@ -313,12 +1775,22 @@ impl SshClient {
                 }
             }
         }
+
+        // Tear down jump hosts from the innermost hop outwards
+        while let Some(jump) = self.jump_sessions.pop() {
+            if let Err(e) = jump.disconnect(Disconnect::ByApplication, "", "English").await {
+                tracing::debug!("Failed to disconnect jump host: {}", e);
+            }
+        }
         Ok(())
     }
 
-    #[allow(dead_code)]
+    /// Whether the target and every jump host connection are still alive.
+    /// A handle closes when its transport fails or keepalives go unanswered.
     pub fn is_connected(&self) -> bool {
-        self.session.is_some()
+        // A session held exclusively is busy requesting a remote forward, not closed
+        self.session.as_ref().is_some_and(|session| session.try_read().map_or(true, |session| !session.is_closed()))
+            && self.jump_sessions.iter().all(|jump| !jump.is_closed())
     }
 
     /// Create a persistent PTY shell session with enhanced safety
@@ -330,7 +1802,7 @@ impl SshClient {
     ) -> Result<PtySession> {
         if let Some(session) = &self.session {
             // Use the enhanced PTY session with proper error handling
-            PtySession::create(session, cols, rows).await
+            PtySession::create(&*session.read().await, cols, rows, &self.channel_setup).await
         } else {
             Err(anyhow::anyhow!("Not connected"))
         }
@@ -339,7 +1811,7 @@ impl SshClient {
     pub async fn download_file(&self, remote_path: &str, local_path: &str) -> Result<u64> {
         if let Some(session) = &self.session {
             // Open SFTP subsystem
-            let channel = session.channel_open_session().await?;
+            let channel = session.read().await.channel_open_session().await?;
             channel.request_subsystem(true, "sftp").await?;
             let sftp = SftpSession::new(channel.into_stream()).await?;
 
@@ -372,7 +1844,7 @@ impl SshClient {
     pub async fn download_file_to_memory(&self, remote_path: &str) -> Result<Vec<u8>> {
         if let Some(session) = &self.session {
             // Open SFTP subsystem
-            let channel = session.channel_open_session().await?;
+            let channel = session.read().await.channel_open_session().await?;
             channel.request_subsystem(true, "sftp").await?;
             let sftp = SftpSession::new(channel.into_stream()).await?;
 
@@ -404,7 +1876,7 @@ impl SshClient {
             let total_bytes = data.len() as u64;
 
             // Open SFTP subsystem
-            let channel = session.channel_open_session().await?;
+            let channel = session.read().await.channel_open_session().await?;
             channel.request_subsystem(true, "sftp").await?;
             let sftp = SftpSession::new(channel.into_stream()).await?;
 
@@ -434,7 +1906,7 @@ impl SshClient {
             let total_bytes = data.len() as u64;
 
             // Open SFTP subsystem
-            let channel = session.channel_open_session().await?;
+            let channel = session.read().await.channel_open_session().await?;
             channel.request_subsystem(true, "sftp").await?;
             let sftp = SftpSession::new(channel.into_stream()).await?;
 
@@ -0,0 +1,114 @@
+use anyhow::Result;
+use std::net::SocketAddr;
+use tokio::net::TcpStream;
+use tokio::task::JoinSet;
+use tokio::time::{Duration, Instant};
+
+/// How long an attempt gets before the next address is tried in parallel (RFC 8305)
+const ATTEMPT_DELAY: Duration = Duration::from_millis(250);
+
+/// Open a TCP connection to `host:port`, racing every resolved address happy-eyeballs style.
+/// `timeout` bounds resolution and all attempts together; the error names each address tried.
+pub async fn connect(host: &str, port: u16, timeout: Option<Duration>) -> Result<TcpStream> {
+    let deadline = timeout.map(|limit| Instant::now() + limit);
+
+    let resolved = match deadline {
+        Some(deadline) => tokio::time::timeout_at(deadline, tokio::net::lookup_host((host, port)))
+            .await
+            .map_err(|_| anyhow::anyhow!("Timed out resolving {}", host))?,
+        None => tokio::net::lookup_host((host, port)).await,
+    };
+    let addrs = interleave(
+        resolved
+            .map_err(|e| anyhow::anyhow!("Failed to resolve {}: {}", host, e))?
+            .collect(),
+    );
+    if addrs.is_empty() {
+        return Err(anyhow::anyhow!("{} did not resolve to any address", host));
+    }
+
+    race(addrs, deadline).await.map_err(|failures| {
+        let tried: Vec<String> = failures
+            .iter()
+            .map(|(addr, reason)| format!("{}: {}", addr, reason))
+            .collect();
+        anyhow::anyhow!("Could not connect to {}:{} ({})", host, port, tried.join("; "))
+    })
+}
+
+/// Alternate address families, starting with whichever the resolver listed first
+pub fn interleave(addrs: Vec<SocketAddr>) -> Vec<SocketAddr> {
+    let first_is_v6 = addrs.first().is_some_and(SocketAddr::is_ipv6);
+    let (preferred, other): (Vec<_>, Vec<_>) = addrs.into_iter().partition(|a| a.is_ipv6() == first_is_v6);
+
+    let mut ordered = Vec::with_capacity(preferred.len() + other.len());
+    let mut other = other.into_iter();
+    for addr in preferred {
+        ordered.push(addr);
+        ordered.extend(other.next());
+    }
+    ordered.extend(other);
+    ordered
+}
+
+/// Start a new attempt every `ATTEMPT_DELAY` (or as soon as one fails) and keep the first
+/// connection to succeed. Losing attempts are aborted when the set is dropped.
+async fn race(addrs: Vec<SocketAddr>, deadline: Option<Instant>) -> std::result::Result<TcpStream, Vec<(SocketAddr, String)>> {
+    let mut pending = addrs.into_iter();
+    let mut in_flight = Vec::new();
+    let mut attempts = JoinSet::new();
+    let mut failures = Vec::new();
+
+    let start = |addr: SocketAddr, attempts: &mut JoinSet<_>, in_flight: &mut Vec<SocketAddr>| {
+        tracing::debug!("Trying {}", addr);
+        in_flight.push(addr);
+        attempts.spawn(async move { (addr, TcpStream::connect(addr).await) });
+    };
+
+    loop {
+        if attempts.is_empty() {
+            match pending.next() {
+                Some(addr) => start(addr, &mut attempts, &mut in_flight),
+                None => break,
+            }
+        }
+
+        tokio::select! {
+            Some(joined) = attempts.join_next() => {
+                let Ok((addr, result)) = joined else { continue };
+                match result {
+                    Ok(stream) => {
+                        tracing::debug!("Connected to {}", addr);
+                        return Ok(stream);
+                    }
+                    Err(e) => {
+                        in_flight.retain(|a| *a != addr);
+                        failures.push((addr, e.to_string()));
+                        if let Some(next) = pending.next() {
+                            start(next, &mut attempts, &mut in_flight);
+                        }
+                    }
+                }
+            }
+            _ = tokio::time::sleep(ATTEMPT_DELAY), if pending.len() > 0 => {
+                if let Some(next) = pending.next() {
+                    start(next, &mut attempts, &mut in_flight);
+                }
+            }
+            _ = sleep_until(deadline) => {
+                failures.extend(in_flight.drain(..).map(|addr| (addr, "timed out".to_string())));
+                failures.extend(pending.by_ref().map(|addr| (addr, "not tried before the timeout".to_string())));
+                break;
+            }
+        }
+    }
+
+    Err(failures)
+}
+
+async fn sleep_until(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline).await,
+        None => std::future::pending().await,
+    }
+}
@@ -0,0 +1,276 @@
+use super::ProxyConfig;
+use anyhow::Result;
+use base64::Engine;
+use std::net::IpAddr;
+use std::pin::Pin;
+use std::process::Stdio;
+use std::task::{Context, Poll};
+use tokio::io::{
+    AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader, Join, ReadBuf,
+};
+use tokio::net::TcpStream;
+use tokio::process::{Child, ChildStdin, ChildStdout, Command};
+use tokio::time::Duration;
+
+/// SSH transport over a local command's stdin/stdout (OpenSSH's ProxyCommand).
+/// The child process is killed when the stream is dropped.
+pub struct ProxyCommandStream {
+    io: Join<ChildStdout, ChildStdin>,
+    _child: Child,
+}
+
+/// Expand `%h` (host), `%p` (port), `%r` (remote user) and `%%` in a ProxyCommand
+pub fn expand_command(template: &str, host: &str, port: u16, username: &str) -> String {
+    let mut expanded = String::with_capacity(template.len());
+    let mut chars = template.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            expanded.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('h') => expanded.push_str(host),
+            Some('p') => expanded.push_str(&port.to_string()),
+            Some('r') => expanded.push_str(username),
+            Some('%') => expanded.push('%'),
+            Some(other) => {
+                expanded.push('%');
+                expanded.push(other);
+            }
+            None => expanded.push('%'),
+        }
+    }
+    expanded
+}
+
+/// Spawn a ProxyCommand through the platform shell and use it as the SSH transport
+pub fn spawn_command(template: &str, host: &str, port: u16, username: &str) -> Result<ProxyCommandStream> {
+    let command_line = expand_command(template, host, port, username);
+    tracing::info!("Starting ProxyCommand: {}", command_line);
+
+    #[cfg(windows)]
+    let mut command = {
+        let mut command = Command::new("cmd");
+        command.arg("/C").arg(&command_line);
+        command
+    };
+    #[cfg(not(windows))]
+    let mut command = {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(&command_line);
+        command
+    };
+
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| anyhow::anyhow!("Failed to start ProxyCommand '{}': {}", command_line, e))?;
+
+    let stdin = child.stdin.take().ok_or_else(|| anyhow::anyhow!("ProxyCommand stdin unavailable"))?;
+    let stdout = child.stdout.take().ok_or_else(|| anyhow::anyhow!("ProxyCommand stdout unavailable"))?;
+
+    // Surface the command's diagnostics (e.g. "connection refused") in our logs
+    if let Some(stderr) = child.stderr.take() {
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(stderr).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                tracing::warn!("[ProxyCommand] {}", line);
+            }
+        });
+    }
+
+    Ok(ProxyCommandStream {
+        io: tokio::io::join(stdout, stdin),
+        _child: child,
+    })
+}
+
+impl AsyncRead for ProxyCommandStream {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.io).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for ProxyCommandStream {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<std::io::Result<usize>> {
+        Pin::new(&mut self.io).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.io).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.io).poll_shutdown(cx)
+    }
+}
+
+/// Open a TCP stream to `host:port` through a SOCKS5 or HTTP CONNECT proxy.
+/// `timeout` applies separately to reaching the proxy and to its handshake.
+pub async fn connect_via(proxy: &ProxyConfig, host: &str, port: u16, timeout: Option<Duration>) -> Result<TcpStream> {
+    let (proxy_host, proxy_port) = match proxy {
+        ProxyConfig::Socks5 { host, port, .. } | ProxyConfig::Http { host, port, .. } => (host, *port),
+    };
+
+    let mut stream = super::net::connect(proxy_host, proxy_port, timeout)
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to connect to proxy: {}", e))?;
+
+    let handshake = async {
+        match proxy {
+            ProxyConfig::Socks5 { username, password, .. } => {
+                let credentials = username.as_deref().map(|u| (u, password.as_deref().unwrap_or("")));
+                socks5_handshake(&mut stream, host, port, credentials).await
+            }
+            ProxyConfig::Http { username, password, .. } => {
+                let credentials = username.as_deref().map(|u| (u, password.as_deref().unwrap_or("")));
+                http_connect_handshake(&mut stream, host, port, credentials).await
+            }
+        }
+    };
+    super::within(timeout, handshake)
+        .await
+        .map_err(|_| anyhow::anyhow!("Proxy {}:{} did not answer in time", proxy_host, proxy_port))??;
+
+    tracing::info!("Proxy {}:{} connected to {}:{}", proxy_host, proxy_port, host, port);
+    Ok(stream)
+}
+
+/// Perform a SOCKS5 CONNECT (RFC 1928) with optional username/password auth (RFC 1929).
+/// Domain names are resolved by the proxy.
+pub async fn socks5_handshake<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut S,
+    host: &str,
+    port: u16,
+    credentials: Option<(&str, &str)>,
+) -> Result<()> {
+    // Greeting: offer "no auth", plus username/password when we have credentials
+    let greeting: &[u8] = if credentials.is_some() { &[5, 2, 0, 2] } else { &[5, 1, 0] };
+    stream.write_all(greeting).await?;
+
+    let mut choice = [0u8; 2];
+    stream.read_exact(&mut choice).await?;
+    if choice[0] != 5 {
+        return Err(anyhow::anyhow!("Proxy is not a SOCKS5 server"));
+    }
+
+    match (choice[1], credentials) {
+        (0x00, _) => {}
+        (0x02, Some((username, password))) => {
+            if username.len() > 255 || password.len() > 255 {
+                return Err(anyhow::anyhow!("SOCKS5 username and password must be at most 255 bytes"));
+            }
+            let mut auth = vec![1, username.len() as u8];
+            auth.extend_from_slice(username.as_bytes());
+            auth.push(password.len() as u8);
+            auth.extend_from_slice(password.as_bytes());
+            stream.write_all(&auth).await?;
+
+            let mut status = [0u8; 2];
+            stream.read_exact(&mut status).await?;
+            if status[1] != 0 {
+                return Err(anyhow::anyhow!("SOCKS5 proxy rejected the username or password"));
+            }
+        }
+        (0x02, None) => return Err(anyhow::anyhow!("SOCKS5 proxy requires a username and password")),
+        _ => return Err(anyhow::anyhow!("SOCKS5 proxy offered no acceptable authentication method")),
+    }
+
+    // CONNECT request
+    let mut request = vec![5, 1, 0];
+    match host.parse::<IpAddr>() {
+        Ok(IpAddr::V4(ip)) => {
+            request.push(1);
+            request.extend_from_slice(&ip.octets());
+        }
+        Ok(IpAddr::V6(ip)) => {
+            request.push(4);
+            request.extend_from_slice(&ip.octets());
+        }
+        Err(_) => {
+            if host.len() > 255 {
+                return Err(anyhow::anyhow!("Host name too long for SOCKS5: {}", host));
+            }
+            request.push(3);
+            request.push(host.len() as u8);
+            request.extend_from_slice(host.as_bytes());
+        }
+    }
+    request.extend_from_slice(&port.to_be_bytes());
+    stream.write_all(&request).await?;
+
+    let mut reply = [0u8; 4];
+    stream.read_exact(&mut reply).await?;
+    if reply[1] != 0 {
+        let reason = match reply[1] {
+            1 => "general failure",
+            2 => "connection not allowed by ruleset",
+            3 => "network unreachable",
+            4 => "host unreachable",
+            5 => "connection refused",
+            6 => "TTL expired",
+            7 => "command not supported",
+            8 => "address type not supported",
+            _ => "unknown error",
+        };
+        return Err(anyhow::anyhow!("SOCKS5 proxy could not connect to {}:{}: {}", host, port, reason));
+    }
+
+    // Skip the bound address the proxy reports back
+    let address_len = match reply[3] {
+        1 => 4,
+        4 => 16,
+        3 => stream.read_u8().await? as usize,
+        other => return Err(anyhow::anyhow!("SOCKS5 proxy sent an invalid address type: {}", other)),
+    };
+    let mut bound = vec![0u8; address_len + 2];
+    stream.read_exact(&mut bound).await?;
+
+    Ok(())
+}
+
+/// Perform an HTTP CONNECT handshake with optional Basic proxy authentication
+pub async fn http_connect_handshake<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut S,
+    host: &str,
+    port: u16,
+    credentials: Option<(&str, &str)>,
+) -> Result<()> {
+    let authority = if host.contains(':') {
+        format!("[{}]:{}", host, port)
+    } else {
+        format!("{}:{}", host, port)
+    };
+
+    let mut request = format!("CONNECT {authority} HTTP/1.1\r\nHost: {authority}\r\n");
+    if let Some((username, password)) = credentials {
+        let token = base64::engine::general_purpose::STANDARD.encode(format!("{}:{}", username, password));
+        request.push_str(&format!("Proxy-Authorization: Basic {}\r\n", token));
+    }
+    request.push_str("\r\n");
+    stream.write_all(request.as_bytes()).await?;
+
+    // Read the response headers one byte at a time so no SSH data is consumed
+    let mut response = Vec::new();
+    while !response.ends_with(b"\r\n\r\n") {
+        if response.len() > 16 * 1024 {
+            return Err(anyhow::anyhow!("HTTP proxy response headers too large"));
+        }
+        response.push(stream.read_u8().await.map_err(|e| {
+            anyhow::anyhow!("HTTP proxy closed the connection during CONNECT: {}", e)
+        })?);
+    }
+
+    let response = String::from_utf8_lossy(&response);
+    let status_line = response.lines().next().unwrap_or_default();
+    let status = status_line.split_whitespace().nth(1).unwrap_or_default();
+    match status {
+        s if s.starts_with('2') => Ok(()),
+        "407" => Err(anyhow::anyhow!("HTTP proxy requires authentication: {}", status_line)),
+        _ => Err(anyhow::anyhow!("HTTP proxy refused CONNECT to {}: {}", authority, status_line)),
+    }
+}
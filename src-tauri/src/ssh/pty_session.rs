@@ -6,6 +6,8 @@ use tokio::io::{AsyncWriteExt};
 use tokio::sync::{mpsc, Mutex, RwLock};
 use tokio::time::{timeout, Duration};
 
+use super::channel_setup::{request_accepted, ChannelSetup};
+
 /// Enhanced PTY session with proper resource management and error handling
 pub struct PtySession {
     pub input_tx: mpsc::Sender<Vec<u8>>,
@@ -19,14 +21,18 @@ pub struct PtySession {
     
     // Terminal state
     terminal_size: Arc<RwLock<(u32, u32)>>, // (cols, rows)
+
+    /// Environment variables the server refused to set
+    pub rejected_env: Vec<String>,
 }
 
 impl PtySession {
     /// Create a new PTY session with enhanced safety features
     pub async fn create(
-        session: &Arc<client::Handle<super::Client>>,
+        session: &client::Handle<super::Client>,
         cols: u32,
         rows: u32,
+        setup: &ChannelSetup,
     ) -> Result<Self> {
         // Validate terminal size
         if cols == 0 || rows == 0 || cols > 1000 || rows > 1000 {
@@ -37,7 +43,7 @@ impl PtySession {
         }
 
         // Open a new SSH channel with timeout
-        let channel = timeout(
+        let mut channel = timeout(
             Duration::from_secs(10),
             session.channel_open_session()
         )
@@ -61,6 +67,11 @@ impl PtySession {
         .await
         .map_err(|_| anyhow::anyhow!("Timeout requesting PTY"))?
         .map_err(|e| anyhow::anyhow!("Failed to request PTY: {}", e))?;
+        if !request_accepted(&mut channel).await? {
+            return Err(anyhow::anyhow!("Server refused to allocate a PTY"));
+        }
+
+        let rejected_env = setup.apply(&mut channel, true).await?;
         
         // Start interactive shell with timeout
         timeout(
@@ -103,6 +114,7 @@ impl PtySession {
             input_task: Arc::new(Mutex::new(Some(input_task))),
             output_task: Arc::new(Mutex::new(Some(output_task))),
             terminal_size,
+            rejected_env,
         })
     }
     
@@ -189,7 +201,8 @@ impl PtySession {
                     }
                     ChannelMsg::ExtendedData { data, .. } => {
                         // stderr data
-                        if timeout(Duration::from_secs(5), output_tx.send(data.to_vec())).await.is_err() {
+                        let sent = timeout(Duration::from_secs(5), output_tx.send(data.to_vec())).await;
+                        if sent.is_err() {
                             break;
                         }
                     }
@@ -216,7 +229,6 @@ impl PtySession {
     }
     
     /// Get current terminal size
-    #[allow(dead_code)]
     pub async fn get_size(&self) -> (u32, u32) {
         *self.terminal_size.read().await
     }
@@ -0,0 +1,146 @@
+use anyhow::Result;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Longest user id or host name accepted in a SOCKS4/4a request
+const MAX_FIELD_LEN: usize = 255;
+
+/// Protocol version a client spoke, so the reply can use the same one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Version {
+    Socks4,
+    Socks5,
+}
+
+/// A CONNECT request received by the local SOCKS server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Request {
+    pub version: Version,
+    pub host: String,
+    pub port: u16,
+}
+
+/// Read a SOCKS4, SOCKS4a or SOCKS5 greeting and CONNECT request.
+/// Only unauthenticated CONNECT is supported; anything else is refused on the wire.
+pub async fn read_request<S>(stream: &mut S) -> Result<Request>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    match stream.read_u8().await? {
+        4 => read_socks4(stream).await,
+        5 => read_socks5(stream).await,
+        version => Err(anyhow::anyhow!("Unsupported SOCKS version {}", version)),
+    }
+}
+
+/// Tell the client whether the tunnel to its destination was opened
+pub async fn reply<S>(stream: &mut S, version: Version, success: bool) -> Result<()>
+where
+    S: AsyncWrite + Unpin,
+{
+    match version {
+        Version::Socks4 => stream.write_all(&[0, if success { 0x5a } else { 0x5b }, 0, 0, 0, 0, 0, 0]).await?,
+        // General failure: the SSH server could not reach the destination
+        Version::Socks5 => socks5_reply(stream, if success { 0 } else { 1 }).await?,
+    }
+    stream.flush().await?;
+    Ok(())
+}
+
+async fn read_socks4<S>(stream: &mut S) -> Result<Request>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let command = stream.read_u8().await?;
+    let port = stream.read_u16().await?;
+    let mut ip = [0u8; 4];
+    stream.read_exact(&mut ip).await?;
+    read_null_terminated(stream).await?; // user id, ignored
+
+    if command != 1 {
+        reply(stream, Version::Socks4, false).await?;
+        return Err(anyhow::anyhow!("Unsupported SOCKS4 command {}", command));
+    }
+
+    // SOCKS4a: an address of 0.0.0.x (x != 0) means a host name follows
+    let host = if ip[..3] == [0, 0, 0] && ip[3] != 0 {
+        read_null_terminated(stream).await?
+    } else {
+        Ipv4Addr::from(ip).to_string()
+    };
+    Ok(Request { version: Version::Socks4, host, port })
+}
+
+async fn read_socks5<S>(stream: &mut S) -> Result<Request>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut methods = vec![0u8; stream.read_u8().await? as usize];
+    stream.read_exact(&mut methods).await?;
+    if !methods.contains(&0) {
+        stream.write_all(&[5, 0xff]).await?;
+        return Err(anyhow::anyhow!("SOCKS5 client requires authentication, which is not supported"));
+    }
+    stream.write_all(&[5, 0]).await?;
+
+    let mut header = [0u8; 4];
+    stream.read_exact(&mut header).await?;
+    let [version, command, _, address_type] = header;
+    if version != 5 {
+        return Err(anyhow::anyhow!("Invalid SOCKS5 request version {}", version));
+    }
+
+    let host = match address_type {
+        1 => {
+            let mut ip = [0u8; 4];
+            stream.read_exact(&mut ip).await?;
+            Ipv4Addr::from(ip).to_string()
+        }
+        3 => {
+            let mut name = vec![0u8; stream.read_u8().await? as usize];
+            stream.read_exact(&mut name).await?;
+            String::from_utf8(name).map_err(|_| anyhow::anyhow!("SOCKS5 host name is not valid UTF-8"))?
+        }
+        4 => {
+            let mut ip = [0u8; 16];
+            stream.read_exact(&mut ip).await?;
+            Ipv6Addr::from(ip).to_string()
+        }
+        _ => {
+            socks5_reply(stream, 8).await?;
+            return Err(anyhow::anyhow!("Unsupported SOCKS5 address type {}", address_type));
+        }
+    };
+    let port = stream.read_u16().await?;
+
+    if command != 1 {
+        socks5_reply(stream, 7).await?;
+        return Err(anyhow::anyhow!("Unsupported SOCKS5 command {}", command));
+    }
+    Ok(Request { version: Version::Socks5, host, port })
+}
+
+/// SOCKS5 reply with an unspecified bound address
+async fn socks5_reply<S>(stream: &mut S, code: u8) -> std::io::Result<()>
+where
+    S: AsyncWrite + Unpin,
+{
+    stream.write_all(&[5, code, 0, 1, 0, 0, 0, 0, 0, 0]).await
+}
+
+async fn read_null_terminated<S>(stream: &mut S) -> Result<String>
+where
+    S: AsyncRead + Unpin,
+{
+    let mut field = Vec::new();
+    loop {
+        match stream.read_u8().await? {
+            0 => break,
+            _ if field.len() == MAX_FIELD_LEN => {
+                return Err(anyhow::anyhow!("SOCKS4 request field is too long"));
+            }
+            byte => field.push(byte),
+        }
+    }
+    String::from_utf8(field).map_err(|_| anyhow::anyhow!("SOCKS4 request field is not valid UTF-8"))
+}
@@ -0,0 +1,676 @@
+use super::known_hosts::wildcard_match;
+use super::{expand_home, AlgorithmPreferences, AuthMethod, DynamicForwardPort, ForwardPort, JumpHost, RemoteForwardPort, SshConfig, StreamLocalForwardPort};
+use anyhow::Result;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Limit for nested Include directives and ProxyJump alias lookups
+const MAX_DEPTH: usize = 16;
+
+/// Default OpenSSH client config location (`~/.ssh/config`)
+pub fn default_path() -> Option<PathBuf> {
+    let home = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE"))?;
+    Some(PathBuf::from(home).join(".ssh").join("config"))
+}
+
+/// When the options of a section apply
+#[derive(Debug, Clone)]
+enum Condition {
+    /// Options before the first `Host` or `Match` line apply to every host
+    Always,
+    /// `Host` patterns, matched against the alias typed by the user
+    Host(Vec<String>),
+    /// `Match` criteria, all of which must hold
+    Match(Vec<Criterion>),
+}
+
+#[derive(Debug, Clone)]
+enum Criterion {
+    All,
+    Final,
+    Host(String),
+    OriginalHost(String),
+    User(String),
+    LocalUser(String),
+    /// `exec`, `canonical` and friends can't be evaluated here and never match
+    Unsupported(String),
+}
+
+#[derive(Debug)]
+struct Section {
+    condition: Condition,
+    /// Lowercased keyword and its raw, unparsed arguments
+    options: Vec<(String, String)>,
+}
+
+/// A parsed ssh_config file with its Includes inlined
+#[derive(Debug, Default)]
+pub struct ConfigFile {
+    sections: Vec<Section>,
+}
+
+/// Options collected for one host; the first value obtained for each wins, as in OpenSSH
+#[derive(Debug, Default)]
+struct HostOptions {
+    host_name: Option<String>,
+    port: Option<u16>,
+    user: Option<String>,
+    identity_files: Vec<String>,
+    certificate_file: Option<String>,
+    /// ProxyJump or ProxyCommand, whichever appears first
+    proxy: Option<ProxySetting>,
+    local_forwards: Vec<ForwardPort>,
+    remote_forwards: Vec<RemoteForwardPort>,
+    dynamic_forwards: Vec<DynamicForwardPort>,
+    /// LocalForwards with a Unix socket on either end
+    stream_local_forwards: Vec<StreamLocalForwardPort>,
+    server_alive_interval: Option<u64>,
+    server_alive_count_max: Option<usize>,
+    connect_timeout: Option<u64>,
+    forward_x11: Option<bool>,
+    forward_agent: Option<bool>,
+    /// SetEnv variables; the first value for each name wins
+    set_env: HashMap<String, String>,
+    algorithms: AlgorithmPreferences,
+}
+
+#[derive(Debug)]
+enum ProxySetting {
+    Jump(String),
+    Command(String),
+}
+
+impl ConfigFile {
+    /// Parse the config at `path`. A missing file is treated as empty.
+    /// Relative Include paths are resolved against the file's directory.
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = match std::fs::read_to_string(path) {
+            Ok(c) => c,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(anyhow::anyhow!("Failed to read {}: {}", path.display(), e)),
+        };
+        let include_dir = path.parent().unwrap_or(Path::new("."));
+        Self::parse_str(&contents, include_dir)
+    }
+
+    /// Parse config contents, resolving relative Include paths against `include_dir`
+    pub fn parse_str(contents: &str, include_dir: &Path) -> Result<Self> {
+        let mut config = Self { sections: Vec::new() };
+        config.parse(contents, include_dir, Condition::Always, 0)?;
+        Ok(config)
+    }
+
+    fn parse(&mut self, contents: &str, include_dir: &Path, mut condition: Condition, depth: usize) -> Result<()> {
+        self.start_section(&condition);
+
+        for line in contents.lines() {
+            let Some((keyword, args)) = split_line(line) else {
+                continue;
+            };
+
+            match keyword.as_str() {
+                "host" => {
+                    condition = Condition::Host(tokenize(args));
+                    self.start_section(&condition);
+                }
+                "match" => {
+                    condition = Condition::Match(parse_match(&tokenize(args)));
+                    self.start_section(&condition);
+                }
+                "include" => {
+                    if depth >= MAX_DEPTH {
+                        return Err(anyhow::anyhow!("Include nested too deeply in ssh config"));
+                    }
+                    for pattern in tokenize(args) {
+                        for path in include_paths(&pattern, include_dir) {
+                            let included = std::fs::read_to_string(&path)
+                                .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", path.display(), e))?;
+                            self.parse(&included, include_dir, condition.clone(), depth + 1)?;
+                        }
+                    }
+                    // Lines after the Include still belong to the enclosing block
+                    self.start_section(&condition);
+                }
+                _ => {
+                    if let Some(section) = self.sections.last_mut() {
+                        section.options.push((keyword, args.to_string()));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn start_section(&mut self, condition: &Condition) {
+        self.sections.push(Section {
+            condition: condition.clone(),
+            options: Vec::new(),
+        });
+    }
+
+    /// Concrete host aliases declared in `Host` lines (patterns and negations are skipped)
+    pub fn aliases(&self) -> Vec<String> {
+        let mut aliases: Vec<String> = Vec::new();
+        for section in &self.sections {
+            let Condition::Host(patterns) = &section.condition else {
+                continue;
+            };
+            for pattern in patterns {
+                if !pattern.contains(['*', '?', '!']) && !aliases.contains(pattern) {
+                    aliases.push(pattern.clone());
+                }
+            }
+        }
+        aliases
+    }
+
+    /// Build the connection settings for `alias`. Each IdentityFile is tried in
+    /// order, followed by the ssh-agent.
+    pub fn resolve(&self, alias: &str) -> Result<SshConfig> {
+        self.resolve_at_depth(alias, 0)
+    }
+
+    fn resolve_at_depth(&self, alias: &str, depth: usize) -> Result<SshConfig> {
+        if depth > MAX_DEPTH {
+            return Err(anyhow::anyhow!("ProxyJump chain for {} is too long", alias));
+        }
+
+        let options = self.lookup(alias)?;
+        let local_user = local_username();
+        let host = host_name(&options, alias);
+        let port = options.port.unwrap_or(22);
+        let username = options.user.clone().or_else(|| local_user.clone()).ok_or_else(|| {
+            anyhow::anyhow!("No User configured for {} and the local user name is unknown", alias)
+        })?;
+
+        // Tokens allowed in IdentityFile and CertificateFile
+        let expand_path = |path: &str| {
+            let expanded = expand_tokens(path, |token| match token {
+                'h' => Some(host.clone()),
+                'n' => Some(alias.to_string()),
+                'p' => Some(port.to_string()),
+                'r' => Some(username.clone()),
+                'u' => local_user.clone(),
+                'd' => std::env::var("HOME").ok(),
+                _ => None,
+            });
+            expand_home(&expanded)
+        };
+
+        // Configured identities first, then whatever the agent holds
+        let mut auth_methods: Vec<AuthMethod> = options
+            .identity_files
+            .iter()
+            .map(|key_path| AuthMethod::PublicKey {
+                key_path: expand_path(key_path),
+                passphrase: None,
+                cert_path: options.certificate_file.as_deref().map(expand_path),
+            })
+            .collect();
+        auth_methods.push(AuthMethod::Agent);
+        let auth_method = auth_methods.remove(0);
+
+        let (jump_hosts, proxy_command) = match &options.proxy {
+            Some(ProxySetting::Jump(value)) if !value.eq_ignore_ascii_case("none") => {
+                (Some(self.resolve_jumps(value, depth)?), None)
+            }
+            Some(ProxySetting::Command(command)) if !command.eq_ignore_ascii_case("none") => {
+                (None, Some(command.clone()))
+            }
+            _ => (None, None),
+        };
+
+        Ok(SshConfig {
+            host,
+            port,
+            username,
+            auth_method,
+            forward_ports: (!options.local_forwards.is_empty()).then_some(options.local_forwards),
+            remote_forward_ports: (!options.remote_forwards.is_empty()).then_some(options.remote_forwards),
+            dynamic_forward_ports: (!options.dynamic_forwards.is_empty()).then_some(options.dynamic_forwards),
+            forward_ids: Vec::new(),
+            stream_local_forward_ports: (!options.stream_local_forwards.is_empty())
+                .then_some(options.stream_local_forwards),
+            jump_hosts,
+            proxy_command,
+            proxy: None,
+            keepalive_interval: options.server_alive_interval,
+            keepalive_max: options.server_alive_count_max,
+            auto_reconnect: None,
+            algorithms: Some(options.algorithms),
+            fallback_auth_methods: (!auth_methods.is_empty()).then_some(auth_methods),
+            connect_timeout: options.connect_timeout,
+            auth_timeout: None,
+            x11_forwarding: options.forward_x11,
+            agent_forwarding: options.forward_agent,
+            confirm_agent_use: None,
+            env: (!options.set_env.is_empty()).then_some(options.set_env),
+            forward_locale: None,
+        })
+    }
+
+    /// Resolve a ProxyJump list (`[user@]host[:port],...`). Each hop is looked up
+    /// in this config too, so bastions can have their own Host blocks.
+    fn resolve_jumps(&self, value: &str, depth: usize) -> Result<Vec<JumpHost>> {
+        let mut jumps = Vec::new();
+        for hop in value.split(',') {
+            let (user, host_port) = match hop.rsplit_once('@') {
+                Some((user, rest)) => (Some(user), rest),
+                None => (None, hop),
+            };
+            let (host, port) = match split_host_port(host_port) {
+                Some((host, port)) => (
+                    host,
+                    Some(port.parse::<u16>().map_err(|_| anyhow::anyhow!("Invalid ProxyJump port in '{}'", hop))?),
+                ),
+                None => (host_port, None),
+            };
+
+            let resolved = self.resolve_at_depth(host, depth + 1)?;
+            jumps.extend(resolved.jump_hosts.unwrap_or_default());
+            jumps.push(JumpHost {
+                host: resolved.host,
+                port: port.unwrap_or(resolved.port),
+                username: user.map(str::to_string).unwrap_or(resolved.username),
+                auth_method: resolved.auth_method,
+                fallback_auth_methods: resolved.fallback_auth_methods,
+            });
+        }
+        Ok(jumps)
+    }
+
+    /// Walk the sections in order, collecting options whose conditions match `alias`
+    fn lookup(&self, alias: &str) -> Result<HostOptions> {
+        let mut options = HostOptions::default();
+        let local_user = local_username().unwrap_or_default();
+
+        for section in &self.sections {
+            let matches = match &section.condition {
+                Condition::Always => true,
+                Condition::Host(patterns) => pattern_list_matches(patterns.iter().map(String::as_str), alias),
+                Condition::Match(criteria) => {
+                    // Match host sees the HostName applied so far, Match user the User
+                    let host = host_name(&options, alias);
+                    let user = options.user.as_deref().unwrap_or(&local_user);
+                    criteria.iter().all(|criterion| match criterion {
+                        Criterion::All | Criterion::Final => true,
+                        Criterion::Host(list) => pattern_list_matches(list.split(','), &host),
+                        Criterion::OriginalHost(list) => pattern_list_matches(list.split(','), alias),
+                        Criterion::User(list) => pattern_list_matches(list.split(','), user),
+                        Criterion::LocalUser(list) => pattern_list_matches(list.split(','), &local_user),
+                        Criterion::Unsupported(name) => {
+                            tracing::debug!("Ignoring unsupported ssh config criterion 'Match {}'", name);
+                            false
+                        }
+                    })
+                }
+            };
+
+            if matches {
+                for (keyword, args) in &section.options {
+                    options.apply(keyword, args).map_err(|e| anyhow::anyhow!("{} (host {})", e, alias))?;
+                }
+            }
+        }
+        Ok(options)
+    }
+}
+
+impl HostOptions {
+    fn apply(&mut self, keyword: &str, args: &str) -> Result<()> {
+        let values = tokenize(args);
+        let Some(value) = values.first() else {
+            return Ok(());
+        };
+
+        match keyword {
+            "hostname" => {
+                self.host_name.get_or_insert_with(|| value.clone());
+            }
+            "port" if self.port.is_none() => {
+                let port = value.parse().map_err(|_| anyhow::anyhow!("Invalid Port '{}'", value))?;
+                self.port = Some(port);
+            }
+            "user" => {
+                self.user.get_or_insert_with(|| value.clone());
+            }
+            "identityfile" if !value.eq_ignore_ascii_case("none") => self.identity_files.push(value.clone()),
+            "certificatefile" => {
+                self.certificate_file.get_or_insert_with(|| value.clone());
+            }
+            "proxyjump" => {
+                self.proxy.get_or_insert_with(|| ProxySetting::Jump(value.clone()));
+            }
+            "proxycommand" => {
+                // Keep the command line as written; it is handed to the shell
+                self.proxy.get_or_insert_with(|| ProxySetting::Command(args.to_string()));
+            }
+            "localforward" if values.iter().any(|v| is_socket_path(v)) => {
+                self.stream_local_forwards.push(parse_stream_local_forward(&values)?)
+            }
+            "localforward" => self.local_forwards.push(parse_local_forward(&values)?),
+            "remoteforward" => self.remote_forwards.push(parse_remote_forward(&values)?),
+            "dynamicforward" => self.dynamic_forwards.push(parse_dynamic_forward(value)?),
+            "kexalgorithms" if self.algorithms.kex.is_none() => self.algorithms.kex = Some(split_list(value)),
+            "hostkeyalgorithms" if self.algorithms.host_key.is_none() => {
+                self.algorithms.host_key = Some(split_list(value))
+            }
+            "ciphers" if self.algorithms.cipher.is_none() => self.algorithms.cipher = Some(split_list(value)),
+            "macs" if self.algorithms.mac.is_none() => self.algorithms.mac = Some(split_list(value)),
+            "serveraliveinterval" if self.server_alive_interval.is_none() => {
+                let interval = value
+                    .parse()
+                    .map_err(|_| anyhow::anyhow!("Invalid ServerAliveInterval '{}'", value))?;
+                self.server_alive_interval = Some(interval);
+            }
+            "serveralivecountmax" if self.server_alive_count_max.is_none() => {
+                let count = value
+                    .parse()
+                    .map_err(|_| anyhow::anyhow!("Invalid ServerAliveCountMax '{}'", value))?;
+                self.server_alive_count_max = Some(count);
+            }
+            "connecttimeout" if self.connect_timeout.is_none() => {
+                let timeout = value
+                    .parse()
+                    .map_err(|_| anyhow::anyhow!("Invalid ConnectTimeout '{}'", value))?;
+                self.connect_timeout = Some(timeout);
+            }
+            "forwardx11" if self.forward_x11.is_none() => self.forward_x11 = Some(parse_yes_no("ForwardX11", value)?),
+            "setenv" => {
+                for assignment in &values {
+                    let (name, value) = assignment
+                        .split_once('=')
+                        .ok_or_else(|| anyhow::anyhow!("Invalid SetEnv '{}'", assignment))?;
+                    self.set_env.entry(name.to_string()).or_insert_with(|| value.to_string());
+                }
+            }
+            // A socket path or environment variable naming another agent is not supported
+            "forwardagent" if self.forward_agent.is_none() => {
+                self.forward_agent = Some(parse_yes_no("ForwardAgent", value)?)
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+/// Split a config line into its lowercased keyword and raw arguments.
+/// Keyword and arguments may be separated by whitespace or `=`.
+fn split_line(line: &str) -> Option<(String, &str)> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+
+    let end = line.find(|c: char| c.is_whitespace() || c == '=').unwrap_or(line.len());
+    let (keyword, rest) = line.split_at(end);
+    let rest = rest.trim_start();
+    let rest = rest.strip_prefix('=').unwrap_or(rest).trim();
+    Some((keyword.to_lowercase(), rest))
+}
+
+/// Split a comma-separated algorithm list. A leading `+`, `-` or `^` stays on the first entry.
+fn split_list(value: &str) -> Vec<String> {
+    value.split(',').map(str::to_string).collect()
+}
+
+/// Split arguments on whitespace, honouring double quotes
+fn tokenize(args: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut in_token = false;
+    let mut in_quotes = false;
+
+    for c in args.chars() {
+        match c {
+            '"' => {
+                in_quotes = !in_quotes;
+                in_token = true;
+            }
+            c if c.is_whitespace() && !in_quotes => {
+                if in_token {
+                    tokens.push(std::mem::take(&mut current));
+                    in_token = false;
+                }
+            }
+            c => {
+                current.push(c);
+                in_token = true;
+            }
+        }
+    }
+    if in_token {
+        tokens.push(current);
+    }
+    tokens
+}
+
+fn parse_match(args: &[String]) -> Vec<Criterion> {
+    let mut criteria = Vec::new();
+    let mut args = args.iter();
+
+    while let Some(name) = args.next() {
+        let mut value = || args.next().cloned().unwrap_or_default();
+        let criterion = match name.to_lowercase().as_str() {
+            "all" => Criterion::All,
+            "final" => Criterion::Final,
+            "host" => Criterion::Host(value()),
+            "originalhost" => Criterion::OriginalHost(value()),
+            "user" => Criterion::User(value()),
+            "localuser" => Criterion::LocalUser(value()),
+            "canonical" => Criterion::Unsupported(name.clone()),
+            _ => {
+                value();
+                Criterion::Unsupported(name.clone())
+            }
+        };
+        criteria.push(criterion);
+    }
+    criteria
+}
+
+/// OpenSSH pattern-list semantics: any negated match rejects, otherwise any positive match accepts
+fn pattern_list_matches<'a>(patterns: impl IntoIterator<Item = &'a str>, value: &str) -> bool {
+    let mut matched = false;
+    for pattern in patterns {
+        if let Some(negated) = pattern.strip_prefix('!') {
+            if wildcard_match(negated, value) {
+                return false;
+            }
+        } else if wildcard_match(pattern, value) {
+            matched = true;
+        }
+    }
+    matched
+}
+
+fn parse_yes_no(keyword: &str, value: &str) -> Result<bool> {
+    match value.to_ascii_lowercase().as_str() {
+        "yes" => Ok(true),
+        "no" => Ok(false),
+        _ => Err(anyhow::anyhow!("Invalid {} '{}'", keyword, value)),
+    }
+}
+
+/// Host to connect to for `alias`: its HostName with `%h` expanded, or the alias itself
+fn host_name(options: &HostOptions, alias: &str) -> String {
+    match &options.host_name {
+        Some(name) => expand_tokens(name, |token| (token == 'h').then(|| alias.to_string())),
+        None => alias.to_string(),
+    }
+}
+
+/// `LocalForward [bind_address:]port host:hostport`
+fn parse_local_forward(values: &[String]) -> Result<ForwardPort> {
+    let [listen, target] = values else {
+        return Err(anyhow::anyhow!("LocalForward needs a listen port and a destination"));
+    };
+
+    let (bind_address, listen_port) = match split_host_port(listen) {
+        Some((bind_address, port)) => (Some(bind_address.to_string()), port),
+        None => (None, listen.as_str()),
+    };
+    let local_port = listen_port
+        .parse()
+        .map_err(|_| anyhow::anyhow!("Invalid LocalForward port '{}'", listen))?;
+    let (remote_host, remote_port) = split_host_port(target)
+        .ok_or_else(|| anyhow::anyhow!("Invalid LocalForward destination '{}'", target))?;
+    let remote_port = remote_port
+        .parse()
+        .map_err(|_| anyhow::anyhow!("Invalid LocalForward destination '{}'", target))?;
+
+    Ok(ForwardPort {
+        bind_address,
+        local_port,
+        remote_host: remote_host.to_string(),
+        remote_port,
+    })
+}
+
+/// `LocalForward [bind_address:]port /remote/socket` or `LocalForward /local/socket /remote/socket`
+fn parse_stream_local_forward(values: &[String]) -> Result<StreamLocalForwardPort> {
+    let [listen, target] = values else {
+        return Err(anyhow::anyhow!("LocalForward needs a listen port and a destination"));
+    };
+    if !is_socket_path(target) {
+        return Err(anyhow::anyhow!(
+            "Forwarding a local socket to '{}' is not supported; the destination must be a socket path",
+            target
+        ));
+    }
+
+    let (bind_address, local_port, local_socket_path) = if is_socket_path(listen) {
+        (None, None, Some(listen.clone()))
+    } else {
+        let (bind_address, listen_port) = match split_host_port(listen) {
+            Some((bind_address, port)) => (Some(bind_address.to_string()), port),
+            None => (None, listen.as_str()),
+        };
+        let local_port = listen_port
+            .parse()
+            .map_err(|_| anyhow::anyhow!("Invalid LocalForward port '{}'", listen))?;
+        (bind_address, Some(local_port), None)
+    };
+
+    Ok(StreamLocalForwardPort {
+        bind_address,
+        local_port,
+        local_socket_path,
+        remote_socket_path: target.clone(),
+    })
+}
+
+/// Forward arguments naming a Unix socket are absolute paths, as in OpenSSH
+fn is_socket_path(value: &str) -> bool {
+    value.starts_with('/') || value.starts_with("~/")
+}
+
+/// `RemoteForward [bind_address:]port host:hostport`
+fn parse_remote_forward(values: &[String]) -> Result<RemoteForwardPort> {
+    let [listen, target] = values else {
+        return Err(anyhow::anyhow!("RemoteForward needs a listen port and a destination"));
+    };
+
+    let (bind_address, listen_port) = match split_host_port(listen) {
+        Some((bind_address, port)) => (Some(bind_address.to_string()), port),
+        None => (None, listen.as_str()),
+    };
+    let remote_port = listen_port
+        .parse()
+        .map_err(|_| anyhow::anyhow!("Invalid RemoteForward port '{}'", listen))?;
+    let (local_host, local_port) = split_host_port(target)
+        .ok_or_else(|| anyhow::anyhow!("Invalid RemoteForward destination '{}'", target))?;
+    let local_port = local_port
+        .parse()
+        .map_err(|_| anyhow::anyhow!("Invalid RemoteForward destination '{}'", target))?;
+
+    Ok(RemoteForwardPort {
+        bind_address,
+        remote_port,
+        local_host: local_host.to_string(),
+        local_port,
+    })
+}
+
+/// `DynamicForward [bind_address:]port`
+fn parse_dynamic_forward(value: &str) -> Result<DynamicForwardPort> {
+    let (bind_address, listen_port) = match split_host_port(value) {
+        Some((bind_address, port)) => (Some(bind_address.to_string()), port),
+        None => (None, value),
+    };
+    let local_port = listen_port
+        .parse()
+        .map_err(|_| anyhow::anyhow!("Invalid DynamicForward port '{}'", value))?;
+    Ok(DynamicForwardPort { bind_address, local_port })
+}
+
+/// Split `host:port`, `[v6]:port` or `host/port`
+fn split_host_port(value: &str) -> Option<(&str, &str)> {
+    if let Some(rest) = value.strip_prefix('[') {
+        let (host, port) = rest.split_once("]:")?;
+        return Some((host, port));
+    }
+    value.rsplit_once(':').or_else(|| value.rsplit_once('/'))
+}
+
+/// Files named by an Include argument. Wildcards are allowed in the file name;
+/// files that don't exist are skipped, as OpenSSH does.
+fn include_paths(pattern: &str, include_dir: &Path) -> Vec<PathBuf> {
+    let expanded = PathBuf::from(expand_home(pattern));
+    let path = if expanded.is_absolute() {
+        expanded
+    } else {
+        include_dir.join(expanded)
+    };
+
+    let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+        return Vec::new();
+    };
+    if !name.contains(['*', '?']) {
+        return if path.is_file() { vec![path] } else { Vec::new() };
+    }
+
+    let Some(dir) = path.parent() else {
+        return Vec::new();
+    };
+    let mut matches: Vec<PathBuf> = std::fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|p| {
+            p.is_file() && p.file_name().and_then(|n| n.to_str()).is_some_and(|n| wildcard_match(name, n))
+        })
+        .collect();
+    matches.sort();
+    matches
+}
+
+/// Replace `%x` tokens using `lookup`; `%%` is a literal percent and unknown tokens are kept
+fn expand_tokens(value: &str, lookup: impl Fn(char) -> Option<String>) -> String {
+    let mut expanded = String::with_capacity(value.len());
+    let mut chars = value.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            expanded.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('%') => expanded.push('%'),
+            Some(token) => match lookup(token) {
+                Some(replacement) => expanded.push_str(&replacement),
+                None => {
+                    expanded.push('%');
+                    expanded.push(token);
+                }
+            },
+            None => expanded.push('%'),
+        }
+    }
+    expanded
+}
+
+fn local_username() -> Option<String> {
+    std::env::var("USER").or_else(|_| std::env::var("USERNAME")).ok()
+}
@@ -0,0 +1,200 @@
+use anyhow::Result;
+use russh::keys::ssh_key::rand_core::{OsRng, RngCore};
+use russh::{client, Channel, ChannelMsg};
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+
+use super::forwards::ForwardStats;
+
+/// The only X11 authentication protocol that is forwarded
+pub const AUTH_PROTOCOL: &str = "MIT-MAGIC-COOKIE-1";
+const COOKIE_LEN: usize = 16;
+/// Display `n` listens on TCP port 6000 + n
+const X11_BASE_PORT: u16 = 6000;
+
+/// How to reach the local X server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DisplayTarget {
+    Unix(String),
+    Tcp { host: String, port: u16 },
+}
+
+/// A parsed `$DISPLAY` value
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Display {
+    pub target: DisplayTarget,
+    pub screen: u32,
+}
+
+/// Parse `[host]:display[.screen]`. An empty host or `unix` means the local socket
+/// `/tmp/.X11-unix/X<display>`; a value starting with `/` is a socket path (as set by XQuartz).
+pub fn parse_display(value: &str) -> Result<Display> {
+    let invalid = || anyhow::anyhow!("Invalid DISPLAY '{}'", value);
+    let (host, number) = value.rsplit_once(':').ok_or_else(invalid)?;
+    let (display, screen) = match number.split_once('.') {
+        Some((display, screen)) => (display, screen.parse().map_err(|_| invalid())?),
+        None => (number, 0),
+    };
+    let display: u16 = display.parse().map_err(|_| invalid())?;
+
+    let target = if host.starts_with('/') {
+        DisplayTarget::Unix(format!("{}:{}", host, display))
+    } else if host.is_empty() || host == "unix" {
+        DisplayTarget::Unix(format!("/tmp/.X11-unix/X{}", display))
+    } else {
+        let port = X11_BASE_PORT.checked_add(display).ok_or_else(invalid)?;
+        DisplayTarget::Tcp { host: host.to_string(), port }
+    };
+    Ok(Display { target, screen })
+}
+
+/// X11 forwarding for one connection. The server is given a random cookie; connections
+/// presenting it get the local display's real cookie instead, so the real one never leaves this machine.
+pub struct X11Forwarding {
+    display: Display,
+    fake_cookie: [u8; COOKIE_LEN],
+    /// Cookie from `xauth` for the local display, if it uses one
+    real_cookie: Option<Vec<u8>>,
+}
+
+impl X11Forwarding {
+    /// Forward to the display named by `$DISPLAY`
+    pub async fn from_env() -> Result<Self> {
+        let value = std::env::var("DISPLAY")
+            .map_err(|_| anyhow::anyhow!("X11 forwarding was requested but DISPLAY is not set"))?;
+        let display = parse_display(&value)?;
+        let real_cookie = local_cookie(&value).await;
+        if real_cookie.is_none() {
+            tracing::debug!("No xauth cookie for display {}, forwarding without one", value);
+        }
+
+        let mut fake_cookie = [0u8; COOKIE_LEN];
+        OsRng.fill_bytes(&mut fake_cookie);
+        Ok(Self { display, fake_cookie, real_cookie })
+    }
+
+    /// Hex cookie sent with the x11-req channel request
+    pub fn fake_cookie_hex(&self) -> String {
+        to_hex(&self.fake_cookie)
+    }
+
+    pub fn screen(&self) -> u32 {
+        self.display.screen
+    }
+
+    /// Check the cookie of a server-opened x11 channel and bridge it to the local display,
+    /// allowing `connect_timeout` to reach a display served over TCP
+    pub async fn forward(&self, mut channel: Channel<client::Msg>, connect_timeout: Duration) -> Result<()> {
+        let mut setup = Vec::new();
+        let packet = loop {
+            match channel.wait().await {
+                Some(ChannelMsg::Data { data }) => {
+                    setup.extend_from_slice(&data);
+                    if let Some(packet) = authenticate_setup(&setup, &self.fake_cookie, self.real_cookie.as_deref())? {
+                        break packet;
+                    }
+                }
+                Some(ChannelMsg::Eof | ChannelMsg::Close) | None => return Ok(()),
+                _ => {}
+            }
+        };
+
+        // X11 traffic is not reported per forward
+        match &self.display.target {
+            DisplayTarget::Tcp { host, port } => {
+                let mut stream = super::net::connect(host, *port, Some(connect_timeout)).await?;
+                stream.write_all(&packet).await?;
+                super::bridge(channel, stream, &ForwardStats::default()).await;
+            }
+            #[cfg(unix)]
+            DisplayTarget::Unix(path) => {
+                let mut stream = tokio::net::UnixStream::connect(path)
+                    .await
+                    .map_err(|e| anyhow::anyhow!("Could not connect to X11 socket {}: {}", path, e))?;
+                stream.write_all(&packet).await?;
+                super::bridge(channel, stream, &ForwardStats::default()).await;
+            }
+            #[cfg(not(unix))]
+            DisplayTarget::Unix(path) => {
+                return Err(anyhow::anyhow!(
+                    "Cannot connect to X11 socket {}: Unix sockets are not supported on this platform",
+                    path
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Validate the authorization in an X11 connection setup and replace the fake cookie with `real`.
+/// Returns `None` until `setup` holds the complete setup header.
+pub fn authenticate_setup(setup: &[u8], fake: &[u8], real: Option<&[u8]>) -> Result<Option<Vec<u8>>> {
+    if setup.len() < 12 {
+        return Ok(None);
+    }
+    let read_u16 = |at: usize| match setup[0] {
+        b'B' => Ok(u16::from_be_bytes([setup[at], setup[at + 1]]) as usize),
+        b'l' => Ok(u16::from_le_bytes([setup[at], setup[at + 1]]) as usize),
+        order => Err(anyhow::anyhow!("Invalid X11 byte order {:#04x}", order)),
+    };
+    let name_len = read_u16(6)?;
+    let data_len = read_u16(8)?;
+    let data_start = 12 + padded(name_len);
+    let end = data_start + padded(data_len);
+    if setup.len() < end {
+        return Ok(None);
+    }
+
+    let name = &setup[12..12 + name_len];
+    let data = &setup[data_start..data_start + data_len];
+    if name != AUTH_PROTOCOL.as_bytes() || data != fake {
+        return Err(anyhow::anyhow!("X11 connection rejected because of wrong authentication"));
+    }
+
+    let cookie = real.unwrap_or(fake);
+    let mut packet = setup[..data_start].to_vec();
+    let cookie_len = u16::try_from(cookie.len()).map_err(|_| anyhow::anyhow!("X11 cookie is too long"))?;
+    let length = match setup[0] {
+        b'B' => cookie_len.to_be_bytes(),
+        _ => cookie_len.to_le_bytes(),
+    };
+    packet[8..10].copy_from_slice(&length);
+    packet.extend_from_slice(cookie);
+    packet.resize(data_start + padded(cookie.len()), 0);
+    // Anything the client sent after the setup goes through unchanged
+    packet.extend_from_slice(&setup[end..]);
+    Ok(Some(packet))
+}
+
+/// Length rounded up to the 4-byte alignment of X11 fields
+fn padded(len: usize) -> usize {
+    len.div_ceil(4) * 4
+}
+
+/// Ask `xauth` for the MIT-MAGIC-COOKIE-1 of a local display
+async fn local_cookie(display: &str) -> Option<Vec<u8>> {
+    let output = tokio::process::Command::new("xauth")
+        .args(["list", display])
+        .output()
+        .await
+        .ok()?;
+    let listing = String::from_utf8_lossy(&output.stdout);
+    listing.lines().find_map(|line| {
+        let mut fields = line.split_whitespace().skip(1);
+        (fields.next()? == AUTH_PROTOCOL).then(|| from_hex(fields.next()?))?
+    })
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
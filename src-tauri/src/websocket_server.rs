@@ -203,14 +203,21 @@ impl WebSocketServer {
                 tracing::info!("Starting PTY session: {} ({}x{})", session_id, cols, rows);
                 
                 // Start the PTY session
-                self.session_manager
+                let rejected_env = self.session_manager
                     .start_pty_session(&session_id, cols, rows)
                     .await?;
 
-                // Send success response
-                let response = WsMessage::Success {
-                    message: format!("PTY session started: {}", session_id),
+                // Send success response, noting variables the server's AcceptEnv didn't allow
+                let message = if rejected_env.is_empty() {
+                    format!("PTY session started: {}", session_id)
+                } else {
+                    format!(
+                        "PTY session started: {} (server rejected environment variables: {})",
+                        session_id,
+                        rejected_env.join(", ")
+                    )
                 };
+                let response = WsMessage::Success { message };
                 tx.send(serde_json::to_string(&response)?)?;
 
                 // Start reading from PTY and sending to WebSocket